@@ -0,0 +1,159 @@
+//! Decoding and encoding of Btrfs LZO compressed extents.
+//!
+//! A compressed extent starts with a LE32 holding the total size of the
+//! extent, header included. It is followed by one segment per sector of
+//! uncompressed data, each made of a LE32 segment size and raw LZO1X data.
+//! Segment headers never cross a sector boundary; if fewer than 4 bytes are
+//! left in a sector, they are padded with zeros.
+//!
+//! ## Example
+//! ```rust
+//! use lzokay_native::btrfs;
+//!
+//! fn main() -> Result<(), lzokay_native::Error> {
+//!     let data = include_bytes!("../test-data/uncompressed/alice29.txt");
+//!
+//!     let extent = btrfs::compress(&data[..0x20000], btrfs::SECTOR_SIZE)?;
+//!     let decompressed = btrfs::decompress(&extent, btrfs::SECTOR_SIZE)?;
+//!     assert_eq!(decompressed, &data[..0x20000]);
+//!
+//!     Ok(())
+//! }
+//! ```
+
+use byteorder::{ByteOrder, LittleEndian};
+
+use crate::compress::{compress_worst_size, Dict};
+use crate::decompress::decompress_all;
+
+/// Default sector size of Btrfs.
+pub const SECTOR_SIZE: usize = 4096;
+
+/* Size of the extent and segment headers */
+const LZO_LEN: usize = 4;
+
+/// Decompresses a Btrfs LZO compressed extent and returns the result as a new [`Vec<u8>`].
+///
+/// Data following the size recorded in the extent header is ignored, as
+/// extents read from disk are padded up to the sector size.
+///
+/// # Arguments
+/// * `data` - Compressed extent
+/// * `sector_size` - Sector size of the filesystem, usually [`SECTOR_SIZE`]
+///
+/// # Errors
+/// Will return [`Err`] if `sector_size` is not a power of two of at least 4
+/// bytes, the extent is truncated, a segment does not decompress or a
+/// segment decompresses to more than one sector.
+pub fn decompress(data: &[u8], sector_size: usize) -> Result<Vec<u8>, crate::Error> {
+    check_sector_size(sector_size)?;
+    if data.len() < LZO_LEN {
+        return Err(crate::Error::InvalidHeader("btrfs extent is truncated"));
+    }
+    let len_in = LittleEndian::read_u32(data) as usize;
+    if len_in < LZO_LEN || len_in > data.len() {
+        return Err(crate::Error::InvalidHeader(
+            "btrfs extent size does not match its header",
+        ));
+    }
+
+    let mut result = Vec::new();
+    let mut cur_in = LZO_LEN;
+    while cur_in < len_in {
+        /* Segment headers never cross a sector boundary */
+        if sector_size - cur_in % sector_size < LZO_LEN {
+            cur_in = cur_in.next_multiple_of(sector_size);
+            if cur_in >= len_in {
+                break;
+            }
+        }
+        if cur_in + LZO_LEN > len_in {
+            return Err(crate::Error::InvalidHeader(
+                "btrfs segment header is truncated",
+            ));
+        }
+
+        let seg_len = LittleEndian::read_u32(&data[cur_in..]) as usize;
+        cur_in += LZO_LEN;
+        if seg_len > len_in - cur_in {
+            return Err(crate::Error::InvalidHeader("btrfs segment is truncated"));
+        }
+
+        let segment = decompress_all(&data[cur_in..cur_in + seg_len], Some(sector_size))?;
+        if segment.len() > sector_size {
+            return Err(crate::Error::OutputOverrun);
+        }
+        result.extend_from_slice(&segment);
+        cur_in += seg_len;
+    }
+
+    Ok(result)
+}
+
+/// Compresses a byte slice into a Btrfs LZO compressed extent and returns the
+/// result as a new [`Vec<u8>`].
+///
+/// Btrfs stores an extent uncompressed if compression does not make it
+/// smaller; callers should compare the result against `data`.
+///
+/// # Arguments
+/// * `data` - Data to compress
+/// * `sector_size` - Sector size of the filesystem, usually [`SECTOR_SIZE`]
+///
+/// # Errors
+/// Will return [`Err`] if `sector_size` is not a power of two of at least 4
+/// bytes or compression fails.
+pub fn compress(data: &[u8], sector_size: usize) -> Result<Vec<u8>, crate::Error> {
+    compress_with_dict(data, sector_size, &mut Dict::new())
+}
+
+/// Compresses a byte slice into a Btrfs LZO compressed extent and returns the
+/// result as a new [`Vec<u8>`], reusing the work memory of `dict`.
+///
+/// # Arguments
+/// * `data` - Data to compress
+/// * `sector_size` - Sector size of the filesystem, usually [`SECTOR_SIZE`]
+/// * `dict` - Data structure to to store data in
+///
+/// # Errors
+/// See [`compress`] for details on possible errors.
+pub fn compress_with_dict(
+    data: &[u8],
+    sector_size: usize,
+    dict: &mut Dict,
+) -> Result<Vec<u8>, crate::Error> {
+    check_sector_size(sector_size)?;
+    let mut result = Vec::with_capacity(
+        LZO_LEN
+            + data.len().div_ceil(sector_size) * (LZO_LEN * 2 + compress_worst_size(sector_size)),
+    );
+    result.extend_from_slice(&[0; LZO_LEN]);
+
+    for sector in data.chunks(sector_size) {
+        let segment = crate::compress::compress_with_dict(sector, dict)?;
+
+        let mut header = [0; LZO_LEN];
+        LittleEndian::write_u32(&mut header, segment.len() as u32);
+        result.extend_from_slice(&header);
+        result.extend_from_slice(&segment);
+
+        /* Pad the sector if the next segment header would not fit */
+        let sector_bytes_left = sector_size - result.len() % sector_size;
+        if sector_bytes_left < LZO_LEN {
+            result.resize(result.len() + sector_bytes_left, 0);
+        }
+    }
+
+    let len = result.len() as u32;
+    LittleEndian::write_u32(&mut result, len);
+
+    Ok(result)
+}
+
+/* Segment headers have to fit in a sector, and sectors are powers of two */
+const fn check_sector_size(sector_size: usize) -> Result<(), crate::Error> {
+    if !sector_size.is_power_of_two() || sector_size < LZO_LEN {
+        return Err(crate::Error::InvalidHeader("invalid btrfs sector size"));
+    }
+    Ok(())
+}
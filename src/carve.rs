@@ -0,0 +1,107 @@
+//! Carving of raw LZO1X streams embedded in binary data.
+//!
+//! [`carve`] tries every offset of the data as the start of a stream and
+//! decompresses it without keeping the output. Candidates are rejected if
+//! they look behind the start of their output or are truncated; streams
+//! reaching the terminating M4 are reported. Streams producing less than a
+//! minimum number of bytes are ignored, as short streams are often found by
+//! chance.
+//!
+//! ## Example
+//! ```rust
+//! use lzokay_native::carve;
+//!
+//! fn main() -> Result<(), lzokay_native::Error> {
+//!     let data = include_bytes!("../test-data/uncompressed/alice29.txt");
+//!
+//!     let mut blob = b"header".to_vec();
+//!     blob.extend_from_slice(&lzokay_native::compress(data)?);
+//!
+//!     let streams = carve::carve(&blob, 1024);
+//!     assert_eq!(streams[0].offset, 6);
+//!     assert_eq!(streams[0].decompressed_len, data.len());
+//!
+//!     Ok(())
+//! }
+//! ```
+
+use std::io::Cursor;
+
+use crate::decompress::{decompress_into, Counter};
+
+/* Number of bytes after the start of a stream that are tried as its start */
+const SYNC_WINDOW: usize = 256;
+
+/// A LZO1X stream found by [`carve`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Carved {
+    /// Offset of the start of the stream
+    pub offset: usize,
+    /// Length of the stream, including the terminating M4
+    pub compressed_len: usize,
+    /// Length of the decompressed data
+    pub decompressed_len: usize,
+}
+
+impl Carved {
+    /// Returns the bytes of the stream in `data`.
+    #[must_use]
+    pub fn slice<'a>(&self, data: &'a [u8]) -> &'a [u8] {
+        &data[self.offset..self.offset + self.compressed_len]
+    }
+}
+
+/// Finds the LZO1X streams embedded in `data`.
+///
+/// Streams don't overlap; the search continues after the end of each stream
+/// found. Of the candidates starting within the first bytes of a stream and
+/// ending with it, the latest one is reported, as bytes in front of a stream
+/// often decode into it.
+///
+/// # Arguments
+/// * `data` - Data to search
+/// * `min_output` - Streams decompressing to fewer bytes are ignored
+#[must_use]
+pub fn carve(data: &[u8], min_output: usize) -> Vec<Carved> {
+    let mut result = Vec::new();
+    let mut offset = 0;
+    while offset < data.len() {
+        let Some(mut carved) = try_offset(data, offset, min_output) else {
+            offset += 1;
+            continue;
+        };
+
+        /* Bytes in front of a stream often decode into the stream, so prefer
+         * the latest start that reaches the same end.
+         */
+        let end = offset + carved.compressed_len;
+        for start in offset + 1..end.min(offset + SYNC_WINDOW) {
+            if let Some(later) = try_offset(data, start, min_output) {
+                if start + later.compressed_len == end {
+                    carved = later;
+                }
+            }
+        }
+
+        result.push(carved);
+        offset = end;
+    }
+
+    result
+}
+
+/* Decompresses a candidate stream at `offset` without keeping the output */
+fn try_offset(data: &[u8], offset: usize, min_output: usize) -> Option<Carved> {
+    let mut reader = Cursor::new(&data[offset..]);
+    let mut counter = Counter::default();
+    decompress_into(&mut reader, &mut counter, false).ok()?;
+    if counter.0 < min_output {
+        return None;
+    }
+
+    Some(Carved {
+        offset,
+        compressed_len: reader.position() as usize,
+        decompressed_len: counter.0,
+    })
+}
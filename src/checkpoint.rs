@@ -0,0 +1,222 @@
+//! Random access into a single raw LZO1X stream.
+//!
+//! A raw stream can't be entered mid-way, as matches reach up to
+//! [`WINDOW_SIZE`] bytes back and the meaning of an instruction depends on
+//! the one before. [`CheckpointIndex::build`] decodes a stream once and
+//! records checkpoints at instruction boundaries, each holding the input and
+//! output offsets, the decoder state and a snapshot of the output preceding
+//! it. [`CheckpointIndex::decompress_range`] resumes decoding from the
+//! nearest checkpoint in front of the requested range.
+//!
+//! ## Example
+//! ```rust
+//! use std::io::Cursor;
+//! use lzokay_native::checkpoint::CheckpointIndex;
+//!
+//! fn main() -> Result<(), lzokay_native::Error> {
+//!     let data = include_bytes!("../test-data/uncompressed/alice29.txt");
+//!     let mut stream = Cursor::new(lzokay_native::compress(data)?);
+//!
+//!     let index = CheckpointIndex::build(&mut stream, 16 * 1024)?;
+//!     let range = index.decompress_range(&mut stream, 100_000..100_016)?;
+//!     assert_eq!(range, data[100_000..100_016]);
+//!
+//!     Ok(())
+//! }
+//! ```
+
+use std::io::{Read, Seek, SeekFrom};
+use std::ops::Range;
+
+use crate::decompress::{decode, InstructionDecoder, Sink};
+
+/// Largest distance of a match, and so the size of the window kept by checkpoints.
+pub const WINDOW_SIZE: usize = 0xbfff;
+
+/// A point at an instruction boundary from which a stream can be decoded.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Checkpoint {
+    /// Position of the next instruction in the reader
+    pub input_offset: u64,
+    /// Number of bytes decoded before the checkpoint
+    pub output_offset: u64,
+    /// State of the decoder, the number of literals copied by the last instruction
+    pub state: usize,
+    /// Up to [`WINDOW_SIZE`] bytes of output preceding the checkpoint
+    pub window: Vec<u8>,
+}
+
+/// Checkpoints of a raw LZO1X stream.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CheckpointIndex {
+    checkpoints: Vec<Checkpoint>,
+    len: u64,
+}
+
+/* Sink keeping only the window needed to decode the following instructions */
+struct Window {
+    data: Vec<u8>,
+    start: usize,
+}
+
+impl Window {
+    fn trim(&mut self) {
+        if self.data.len() > 2 * WINDOW_SIZE {
+            let excess = self.data.len() - WINDOW_SIZE;
+            self.data.drain(..excess);
+            self.start += excess;
+        }
+    }
+
+    fn snapshot(&self) -> Vec<u8> {
+        self.data[self.data.len().saturating_sub(WINDOW_SIZE)..].to_vec()
+    }
+}
+
+impl Sink for Window {
+    fn len(&self) -> usize {
+        self.start + self.data.len()
+    }
+
+    fn literals<I>(&mut self, reader: &mut I, len: usize) -> Result<(), crate::Error>
+    where
+        I: Read,
+    {
+        self.data.literals(reader, len)?;
+        self.trim();
+        Ok(())
+    }
+
+    fn lookback(&mut self, distance: usize, len: usize) -> Result<(), crate::Error> {
+        self.data.lookback(distance, len)?;
+        self.trim();
+        Ok(())
+    }
+
+    fn zeros(&mut self, len: usize) -> Result<(), crate::Error> {
+        self.data.zeros(len)?;
+        self.trim();
+        Ok(())
+    }
+}
+
+impl CheckpointIndex {
+    /// Decodes the stream starting at the position of `reader` and records a
+    /// checkpoint about every `interval` bytes of output.
+    ///
+    /// Each checkpoint holds up to [`WINDOW_SIZE`] bytes, so `interval`
+    /// should be a good deal larger than that.
+    ///
+    /// # Errors
+    /// Will return [`Err`] if there was an I/O error or the stream does not decompress.
+    pub fn build<I>(reader: &mut I, interval: usize) -> Result<Self, crate::Error>
+    where
+        I: Read + Seek,
+    {
+        let interval = interval.max(1);
+        let mut decoder = InstructionDecoder::new(false);
+        let mut window = Window {
+            data: Vec::new(),
+            start: 0,
+        };
+        let mut checkpoints = vec![Checkpoint {
+            input_offset: reader.stream_position()?,
+            output_offset: 0,
+            state: 0,
+            window: Vec::new(),
+        }];
+
+        let mut next = interval;
+        while !decode(reader, &mut decoder, &mut window, |_, w| w.len() >= next)? {
+            checkpoints.push(Checkpoint {
+                input_offset: reader.stream_position()?,
+                output_offset: window.len() as u64,
+                state: decoder.state,
+                window: window.snapshot(),
+            });
+            next = window.len() + interval;
+        }
+
+        Ok(Self {
+            checkpoints,
+            len: window.len() as u64,
+        })
+    }
+
+    /// Returns the checkpoints, ordered by offset.
+    #[must_use]
+    pub fn checkpoints(&self) -> &[Checkpoint] {
+        &self.checkpoints
+    }
+
+    /// Returns the uncompressed size of the stream.
+    #[must_use]
+    pub const fn len(&self) -> u64 {
+        self.len
+    }
+
+    /// Returns `true` if the stream decompresses to nothing.
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the last checkpoint at or before `output_offset`.
+    #[must_use]
+    pub fn nearest(&self, output_offset: u64) -> &Checkpoint {
+        let index = self
+            .checkpoints
+            .partition_point(|cp| cp.output_offset <= output_offset);
+        &self.checkpoints[index.saturating_sub(1)]
+    }
+
+    /// Decompresses a range of the output, decoding from the nearest checkpoint.
+    ///
+    /// # Arguments
+    /// * `reader` - Reader of the stream the index was built from
+    /// * `range` - Range of the uncompressed data to return
+    ///
+    /// # Errors
+    /// Will return [`Error::RangeOutOfBounds`](crate::Error::RangeOutOfBounds)
+    /// if the range is reversed or ends past the output, and [`Err`] if there
+    /// was an I/O error or the stream does not decompress.
+    pub fn decompress_range<I>(
+        &self,
+        reader: &mut I,
+        range: Range<u64>,
+    ) -> Result<Vec<u8>, crate::Error>
+    where
+        I: Read + Seek,
+    {
+        if range.start > range.end || range.end > self.len() {
+            return Err(crate::Error::RangeOutOfBounds {
+                start: range.start,
+                end: range.end,
+            });
+        }
+
+        let checkpoint = self.nearest(range.start);
+        reader.seek(SeekFrom::Start(checkpoint.input_offset))?;
+        let mut decoder = InstructionDecoder {
+            state: checkpoint.state,
+            first: checkpoint.output_offset == 0,
+            rle: false,
+            eof_end: false,
+        };
+
+        let mut output = checkpoint.window.clone();
+        let base = checkpoint.output_offset - output.len() as u64;
+        let end = (range.end - base) as usize;
+        decode(reader, &mut decoder, &mut output, |_, o| o.len() >= end)?;
+        if output.len() < end {
+            return Err(crate::Error::SizeMismatch {
+                expected: end,
+                actual: output.len(),
+            });
+        }
+
+        output.truncate(end);
+        output.drain(..(range.start - base) as usize);
+        Ok(output)
+    }
+}
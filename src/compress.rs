@@ -1,7 +1,5 @@
-use std::{
-    intrinsics::{copy_nonoverlapping, write_bytes},
-    ptr::null,
-};
+use std::marker::PhantomData;
+use std::ptr::{copy_nonoverlapping, null, write_bytes};
 
 use crate::{
     util::{
@@ -79,7 +77,7 @@ pub fn compress_with_dict(data: &[u8], dict: &mut Dict) -> Result<Vec<u8>, crate
         let src_buf = std::ptr::addr_of!(data[0]);
         let dst_buf = dst.as_mut_ptr();
         let mut size: usize = 0;
-        lzokay_compress_dict(src_buf, data.len(), dst_buf, worst, &mut size, dict)?;
+        lzokay_compress_dict(src_buf, data.len(), dst_buf, worst, &raw mut size, dict)?;
 
         dst.set_len(size);
         Ok(dst)
@@ -125,8 +123,8 @@ struct State {
 
 unsafe fn std_mismatch(mut first1: *mut u8, last1: *mut u8, mut first2: *mut u8) -> *mut u8 {
     while first1 != last1 && u32::from(*first1) == u32::from(*first2) {
-        first1 = first1.offset(1);
-        first2 = first2.offset(1);
+        first1 = first1.add(1);
+        first2 = first2.add(1);
     }
     first1
 }
@@ -163,7 +161,7 @@ impl State {
                 *buf.offset((0xbfff_u32 + 0x800_u32).wrapping_add(self.wind_e) as isize) =
                     *self.inp;
             }
-            self.inp = self.inp.offset(1);
+            self.inp = self.inp.add(1);
         }
         self.wind_e = self.wind_e.wrapping_add(1);
         if self.wind_e == 0xbfff_u32 + 0x800_u32 {
@@ -172,10 +170,10 @@ impl State {
         self.wind_b = self.wind_b.wrapping_add(1);
         if self.wind_b == 0xbfff_u32 + 0x800_u32 {
             self.wind_b = 0;
-        };
+        }
     }
 
-    unsafe fn pos2off(&mut self, pos: u32) -> u32 {
+    const unsafe fn pos2off(&mut self, pos: u32) -> u32 {
         if self.wind_b > pos {
             self.wind_b.wrapping_sub(pos)
         } else {
@@ -186,9 +184,9 @@ impl State {
 
 impl Match3 {
     const unsafe fn make_key(data: *const u8) -> u32 {
-        let data_0 = *data.offset(0) as u32;
-        let data_1 = *data.offset(1) as u32;
-        let data_2 = *data.offset(2) as u32;
+        let data_0 = *data as u32;
+        let data_1 = *data.add(1) as u32;
+        let data_2 = *data.add(2) as u32;
 
         ((0x9f5f_u32.wrapping_mul(((data_0 << 5 ^ data_1) << 5) ^ data_2)) >> 5) & 0x3fff_u32
     }
@@ -235,7 +233,7 @@ impl Match3 {
 
 impl Match2 {
     const unsafe fn make_key(data: *const u8) -> u32 {
-        *data.offset(0) as u32 ^ ((*data.offset(1) as u32) << 8)
+        *data as u32 ^ ((*data.add(1) as u32) << 8)
     }
     unsafe fn init(&mut self) {
         self.head = vec![65535_u16; 65536];
@@ -251,7 +249,7 @@ impl Match2 {
             as isize,));
         if u32::from(*p) == pos {
             *p = 65535_u16;
-        };
+        }
     }
     unsafe fn search(
         &mut self,
@@ -265,8 +263,8 @@ impl Match2 {
         if pos == 65535 {
             return false;
         }
-        if *best_pos.offset(2) == 0 {
-            *best_pos.offset(2) = u32::from(pos) + 1;
+        if *best_pos.add(2) == 0 {
+            *best_pos.add(2) = u32::from(pos) + 1;
         }
         if *lb_len < 2 {
             *lb_len = 2;
@@ -324,7 +322,7 @@ impl Dict {
                 0,
                 3,
             );
-        };
+        }
     }
     unsafe fn reset_next_input_entry(&mut self, s: &mut State) {
         /* Remove match from about-to-be-clobbered buffer entry */
@@ -333,7 +331,7 @@ impl Dict {
             self.match2.remove(s.wind_e, self.buffer.as_mut_ptr());
         } else {
             s.cycle1_countdown = s.cycle1_countdown.wrapping_sub(1);
-        };
+        }
     }
     unsafe fn advance(
         &mut self,
@@ -361,8 +359,8 @@ impl Dict {
         let mut match_count: u32 = 0;
         self.match3.advance(
             s,
-            &mut match_pos,
-            &mut match_count,
+            &raw mut match_pos,
+            &raw mut match_count,
             self.buffer.as_mut_ptr(),
         );
         let mut best_char: i32 = i32::from(self.buffer[s.wind_b as usize]);
@@ -376,7 +374,7 @@ impl Dict {
         } else {
             if u32::from(self.match2.search(
                 s,
-                &mut lb_pos,
+                &raw mut lb_pos,
                 lb_len,
                 best_pos.as_mut_ptr(),
                 self.buffer.as_mut_ptr(),
@@ -416,16 +414,16 @@ impl Dict {
                 (::std::mem::size_of::<[u32; 34]>()).wrapping_div(::std::mem::size_of::<u32>()),
             ));
 
-            let mut offit: *mut u32 = best_off.offset(2);
-            let mut posit: *const u32 = best_pos.as_mut_ptr().offset(2);
+            let mut offit: *mut u32 = best_off.add(2);
+            let mut posit: *const u32 = best_pos.as_mut_ptr().add(2);
             while posit < end_best_pos {
                 *offit = if *posit > 0 {
                     s.pos2off((*posit).wrapping_sub(1))
                 } else {
                     0
                 };
-                posit = posit.offset(1);
-                offit = offit.offset(1);
+                posit = posit.add(1);
+                offit = offit.add(1);
             }
         }
         self.reset_next_input_entry(s);
@@ -476,7 +474,7 @@ unsafe fn find_better_match(best_off: *const u32, p_lb_len: *mut u32, p_lb_off:
     {
         *p_lb_len = (*p_lb_len).wrapping_sub(1);
         *p_lb_off = *best_off.offset(*p_lb_len as isize);
-    };
+    }
 }
 unsafe fn encode_literal_run(
     outpp: *mut *mut u8,
@@ -487,41 +485,41 @@ unsafe fn encode_literal_run(
     lit_len: u32,
 ) -> Result<(), Error> {
     let mut outp: *mut u8 = *outpp;
-    if outp == dst as *mut u8 && lit_len <= 238 {
-        if outp.offset(1) > outp_end as *mut u8 {
+    if outp == dst.cast_mut() && lit_len <= 238 {
+        if outp.add(1) > outp_end.cast_mut() {
             *dst_size = outp.offset_from(dst) as usize;
             return Err(Error::OutputOverrun);
         }
         *outp = 17u32.wrapping_add(lit_len) as u8;
-        outp = outp.offset(1);
+        outp = outp.add(1);
     } else if lit_len <= 3 {
-        *outp.offset(-2) = (u32::from(*outp.offset(-2)) | lit_len) as u8;
+        *outp.sub(2) = (u32::from(*outp.sub(2)) | lit_len) as u8;
     } else if lit_len <= 18 {
-        if outp.offset(1) > outp_end as *mut u8 {
+        if outp.add(1) > outp_end.cast_mut() {
             *dst_size = outp.offset_from(dst) as usize;
             return Err(Error::OutputOverrun);
         }
         *outp = lit_len.wrapping_sub(3) as u8;
-        outp = outp.offset(1);
+        outp = outp.add(1);
     } else {
         if outp.offset(lit_len.wrapping_sub(18).wrapping_div(255).wrapping_add(2) as isize)
-            > outp_end as *mut u8
+            > outp_end.cast_mut()
         {
             *dst_size = outp.offset_from(dst) as usize;
             return Err(Error::OutputOverrun);
         }
         *outp = 0;
-        outp = outp.offset(1);
+        outp = outp.add(1);
         let mut l = lit_len.wrapping_sub(18);
         while l > 255 {
             *outp = 0;
-            outp = outp.offset(1);
+            outp = outp.add(1);
             l = l.wrapping_sub(255);
         }
         *outp = l as u8;
-        outp = outp.offset(1);
+        outp = outp.add(1);
     }
-    if outp.offset(lit_len as isize) > outp_end as *mut u8 {
+    if outp.offset(lit_len as isize) > outp_end.cast_mut() {
         *dst_size = outp.offset_from(dst) as usize;
         return Err(Error::OutputOverrun);
     }
@@ -544,106 +542,286 @@ unsafe fn encode_lookback_match(
     let mut outp: *mut u8 = *outpp;
     if lb_len == 2 {
         lb_off = lb_off.wrapping_sub(1);
-        if outp.offset(2) > outp_end as *mut u8 {
+        if outp.add(2) > outp_end.cast_mut() {
             *dst_size = outp.offset_from(dst) as usize;
             return Err(Error::OutputOverrun);
         }
         *outp = (M1_MARKER | ((lb_off & 0x3) << 2)) as u8;
-        outp = outp.offset(1);
+        outp = outp.add(1);
         *outp = (lb_off >> 2) as u8;
     } else if lb_len <= M2_MAX_LEN && lb_off <= M2_MAX_OFFSET {
         lb_off = lb_off.wrapping_sub(1);
-        if outp.offset(2) > outp_end as *mut u8 {
+        if outp.add(2) > outp_end.cast_mut() {
             *dst_size = outp.offset_from(dst) as usize;
             return Err(Error::OutputOverrun);
         }
         *outp = (lb_len.wrapping_sub(1) << 5 | ((lb_off & 0x7) << 2)) as u8;
-        outp = outp.offset(1);
+        outp = outp.add(1);
         *outp = (lb_off >> 3) as u8;
     } else if lb_len == M2_MIN_LEN
         && lb_off <= M1_MAX_OFFSET.wrapping_add(M2_MAX_OFFSET)
         && last_lit_len >= 4
     {
         lb_off = lb_off.wrapping_sub(1_u32.wrapping_add(M2_MAX_OFFSET));
-        if outp.offset(2) > outp_end as *mut u8 {
+        if outp.add(2) > outp_end.cast_mut() {
             *dst_size = outp.offset_from(dst) as usize;
             return Err(Error::OutputOverrun);
         }
         *outp = (M1_MARKER | ((lb_off & 0x3) << 2)) as u8;
-        outp = outp.offset(1);
+        outp = outp.add(1);
         *outp = (lb_off >> 2) as u8;
     } else if lb_off <= M3_MAX_OFFSET {
         lb_off = lb_off.wrapping_sub(1);
         if lb_len <= M3_MAX_LEN {
-            if outp.offset(1) > outp_end as *mut u8 {
+            if outp.add(1) > outp_end.cast_mut() {
                 *dst_size = outp.offset_from(dst) as usize;
                 return Err(Error::OutputOverrun);
             }
             *outp = (M3_MARKER | lb_len.wrapping_sub(2)) as u8;
         } else {
             lb_len = lb_len.wrapping_sub(M3_MAX_LEN);
-            if outp.offset(lb_len.wrapping_div(255).wrapping_add(2) as isize) > outp_end as *mut u8
+            if outp.offset(lb_len.wrapping_div(255).wrapping_add(2) as isize) > outp_end.cast_mut()
             {
                 *dst_size = outp.offset_from(dst) as usize;
                 return Err(Error::OutputOverrun);
             }
             *outp = M3_MARKER as u8;
-            outp = outp.offset(1);
+            outp = outp.add(1);
             let mut l = lb_len;
             while l > 255 {
                 *outp = 0;
-                outp = outp.offset(1);
+                outp = outp.add(1);
                 l = l.wrapping_sub(255);
             }
             *outp = l as u8;
         }
-        outp = outp.offset(1);
-        if outp.offset(2) > outp_end as *mut u8 {
+        outp = outp.add(1);
+        if outp.add(2) > outp_end.cast_mut() {
             *dst_size = outp.offset_from(dst) as usize;
             return Err(Error::OutputOverrun);
         }
         *outp = (lb_off << 2) as u8;
-        outp = outp.offset(1);
+        outp = outp.add(1);
         *outp = (lb_off >> 6) as u8;
     } else {
         lb_off = lb_off.wrapping_sub(0x4000);
         if lb_len <= M4_MAX_LEN {
-            if outp.offset(1) > outp_end as *mut u8 {
+            if outp.add(1) > outp_end.cast_mut() {
                 *dst_size = outp.offset_from(dst) as usize;
                 return Err(Error::OutputOverrun);
             }
             *outp = (M4_MARKER | ((lb_off & 0x4000) >> 11) | lb_len.wrapping_sub(2)) as u8;
         } else {
             lb_len = lb_len.wrapping_sub(M4_MAX_LEN);
-            if outp.offset(lb_len.wrapping_div(255).wrapping_add(2) as isize) > outp_end as *mut u8
+            if outp.offset(lb_len.wrapping_div(255).wrapping_add(2) as isize) > outp_end.cast_mut()
             {
                 *dst_size = outp.offset_from(dst) as usize;
                 return Err(Error::OutputOverrun);
             }
             *outp = (M4_MARKER | ((lb_off & 0x4000) >> 11)) as u8;
-            outp = outp.offset(1);
+            outp = outp.add(1);
             let mut l_0 = lb_len;
             while l_0 > 255 {
                 *outp = 0;
-                outp = outp.offset(1);
+                outp = outp.add(1);
                 l_0 = l_0.wrapping_sub(255);
             }
             *outp = l_0 as u8;
         }
-        outp = outp.offset(1);
-        if outp.offset(2) > outp_end as *mut u8 {
+        outp = outp.add(1);
+        if outp.add(2) > outp_end.cast_mut() {
             *dst_size = outp.offset_from(dst) as usize;
             return Err(Error::OutputOverrun);
         }
         *outp = (lb_off << 2) as u8;
-        outp = outp.offset(1);
+        outp = outp.add(1);
         *outp = (lb_off >> 6) as u8;
     }
-    outp = outp.offset(1);
+    outp = outp.add(1);
     *outpp = outp;
     Ok(())
 }
 
+/// A token of the LZ77 parse chosen by the compressor.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Token<'a> {
+    /// Run of bytes copied to the output as they are, never empty
+    Literals(&'a [u8]),
+    /// Copy of `len` bytes from `offset` bytes back in the output
+    Match {
+        /// Number of bytes copied
+        len: usize,
+        /// Distance back from the end of the output
+        offset: usize,
+    },
+}
+
+/// Returns an iterator over the LZ77 parse the compressor chooses for `data`.
+///
+/// Use [`parse_with_dict`] together with [`Dict`] to avoid repeated
+/// allocations / deallocations across multiple runs.
+///
+/// # Arguments
+/// * `data` - Data to parse
+///
+/// # Example
+/// ```rust
+/// let data = include_bytes!("../test-data/uncompressed/alice29.txt");
+///
+/// let matches = lzokay_native::parse(data)
+///     .filter(|token| matches!(token, lzokay_native::Token::Match { .. }))
+///     .count();
+/// ```
+///
+#[must_use]
+pub fn parse(data: &[u8]) -> Parser<'_> {
+    Parser::new(data, DictRef::Owned(Box::default()))
+}
+
+/// Returns an iterator over the LZ77 parse the compressor chooses for `data`,
+/// reusing the work memory of `dict`.
+///
+/// The tokens are the ones [`compress_with_dict`] encodes, in stream order.
+///
+/// # Arguments
+/// * `data` - Data to parse
+/// * `dict` - Data structure to to store data in
+///
+#[must_use]
+pub fn parse_with_dict<'a>(data: &'a [u8], dict: &'a mut Dict) -> Parser<'a> {
+    Parser::new(data, DictRef::Borrowed(dict))
+}
+
+#[derive(Debug)]
+enum DictRef<'a> {
+    Owned(Box<Dict>),
+    Borrowed(&'a mut Dict),
+}
+
+impl DictRef<'_> {
+    fn get(&mut self) -> &mut Dict {
+        match self {
+            DictRef::Owned(dict) => dict,
+            DictRef::Borrowed(dict) => dict,
+        }
+    }
+}
+
+/// Iterator over the LZ77 parse chosen by the compressor, created by
+/// [`parse`] or [`parse_with_dict`].
+#[derive(Debug)]
+pub struct Parser<'a> {
+    dict: DictRef<'a>,
+    s: State,
+    emitted: bool,
+    lit_ptr: *const u8,
+    lit_len: u32,
+    lb_off: u32,
+    lb_len: u32,
+    best_off: [u32; 34],
+    /* Match to return after the literal run in front of it */
+    pending: Option<Token<'a>>,
+    _data: PhantomData<&'a [u8]>,
+}
+
+impl<'a> Parser<'a> {
+    fn new(data: &'a [u8], dict: DictRef<'a>) -> Self {
+        let mut parser = Self {
+            dict,
+            s: State::new(),
+            emitted: false,
+            lit_ptr: data.as_ptr(),
+            lit_len: 0,
+            lb_off: 0,
+            lb_len: 0,
+            best_off: [0; 34],
+            pending: None,
+            _data: PhantomData,
+        };
+        if !data.is_empty() {
+            unsafe {
+                parser
+                    .dict
+                    .get()
+                    .init(&mut parser.s, data.as_ptr(), data.len());
+                parser.lit_ptr = parser.s.inp;
+                parser.advance(false);
+            }
+        }
+        parser
+    }
+
+    unsafe fn advance(&mut self, skip: bool) {
+        self.dict.get().advance(
+            &mut self.s,
+            &raw mut self.lb_off,
+            &raw mut self.lb_len,
+            self.best_off.as_mut_ptr(),
+            skip,
+        );
+    }
+
+    /* Takes the pending literal run, if there is one */
+    unsafe fn take_literals(&mut self) -> Option<Token<'a>> {
+        let lit_len = std::mem::take(&mut self.lit_len);
+        (lit_len > 0)
+            .then(|| Token::Literals(std::slice::from_raw_parts(self.lit_ptr, lit_len as usize)))
+    }
+}
+
+impl<'a> Iterator for Parser<'a> {
+    type Item = Token<'a>;
+
+    fn next(&mut self) -> Option<Token<'a>> {
+        if let Some(token) = self.pending.take() {
+            return Some(token);
+        }
+
+        unsafe {
+            while self.s.buf_sz > 0 {
+                if self.lit_len == 0 {
+                    self.lit_ptr = self.s.bufp;
+                }
+                let (lit_len, lb_off, lb_len) = (self.lit_len, self.lb_off, self.lb_len);
+                if (lb_len < 2
+                    || lb_len == 2 && (lb_off > M1_MAX_OFFSET || lit_len == 0 || lit_len >= 4)
+                    || lb_len == 2 && !self.emitted
+                    || !self.emitted && lit_len == 0)
+                    || (lb_len == M2_MIN_LEN
+                        && lb_off > M1_MAX_OFFSET.wrapping_add(M2_MAX_OFFSET)
+                        && lit_len >= 4)
+                {
+                    self.lb_len = 0;
+                }
+                if self.lb_len == 0 {
+                    self.lit_len = self.lit_len.wrapping_add(1);
+                    self.advance(false);
+                } else {
+                    find_better_match(
+                        self.best_off.as_ptr(),
+                        &raw mut self.lb_len,
+                        &raw mut self.lb_off,
+                    );
+                    let token = Token::Match {
+                        len: self.lb_len as usize,
+                        offset: self.lb_off as usize,
+                    };
+                    self.emitted = true;
+
+                    let literals = self.take_literals();
+                    self.advance(true);
+                    if literals.is_some() {
+                        self.pending = Some(token);
+                        return literals;
+                    }
+                    return Some(token);
+                }
+            }
+            self.take_literals()
+        }
+    }
+}
+
 unsafe fn lzokay_compress_dict(
     src: *const u8,
     src_size: usize,
@@ -652,89 +830,49 @@ unsafe fn lzokay_compress_dict(
     dst_size: *mut usize,
     dict_storage: &mut Dict,
 ) -> Result<(), Error> {
-    //let mut err: Result<(), Error> = Ok(());
-    let mut s: State = State::new();
     *dst_size = init_dst_size;
     let mut outp: *mut u8 = dst;
     let outp_end: *mut u8 = dst.add(init_dst_size);
-    let mut lit_len: u32 = 0;
-    let mut lb_off: u32 = 0;
-    let mut lb_len: u32 = 0;
-    let mut best_off: [u32; 34] = [0; 34];
-    dict_storage.init(&mut s, src, src_size);
-    let mut lit_ptr: *const u8 = s.inp;
-    dict_storage.advance(
-        &mut s,
-        &mut lb_off,
-        &mut lb_len,
-        best_off.as_mut_ptr(),
-        false,
-    );
-    while s.buf_sz > 0 {
-        if lit_len == 0 {
-            lit_ptr = s.bufp;
-        }
-        // if lb_len < 2
-        //     || lb_len == 2 && (lb_off > M1_MAX_OFFSET || lit_len == 0 || lit_len >= 4)
-        //     || lb_len == 2 && outp == dst
-        //     || outp == dst && lit_len == 0
-        // {
-        //     lb_len = 0
-        // } else if lb_len == M2_MIN_LEN
-        //     && lb_off > M1_MAX_OFFSET.wrapping_add(M2_MAX_OFFSET)
-        //     && lit_len >= 4
-        // {
-        if (lb_len < 2
-            || lb_len == 2 && (lb_off > M1_MAX_OFFSET || lit_len == 0 || lit_len >= 4)
-            || lb_len == 2 && outp == dst
-            || outp == dst && lit_len == 0)
-            || (lb_len == M2_MIN_LEN
-                && lb_off > M1_MAX_OFFSET.wrapping_add(M2_MAX_OFFSET)
-                && lit_len >= 4)
-        {
-            lb_len = 0;
-        }
-        if lb_len == 0 {
-            lit_len = lit_len.wrapping_add(1);
-            dict_storage.advance(
-                &mut s,
-                &mut lb_off,
-                &mut lb_len,
-                best_off.as_mut_ptr(),
-                false,
-            );
-        } else {
-            find_better_match(
-                best_off.as_mut_ptr() as *const u32,
-                &mut lb_len,
-                &mut lb_off,
-            );
-            encode_literal_run(&mut outp, outp_end, dst, dst_size, lit_ptr, lit_len)?;
-
-            encode_lookback_match(&mut outp, outp_end, dst, dst_size, lb_len, lb_off, lit_len)?;
-
-            lit_len = 0;
-            dict_storage.advance(
-                &mut s,
-                &mut lb_off,
-                &mut lb_len,
-                best_off.as_mut_ptr(),
-                true,
-            );
+    let mut last_lit_len: u32 = 0;
+    let data = std::slice::from_raw_parts(src, src_size);
+    for token in parse_with_dict(data, dict_storage) {
+        match token {
+            Token::Literals(lit) => {
+                last_lit_len = lit.len() as u32;
+                encode_literal_run(
+                    &raw mut outp,
+                    outp_end,
+                    dst,
+                    dst_size,
+                    lit.as_ptr(),
+                    last_lit_len,
+                )?;
+            }
+            Token::Match { len, offset } => {
+                let lit_len = std::mem::take(&mut last_lit_len);
+                encode_lookback_match(
+                    &raw mut outp,
+                    outp_end,
+                    dst,
+                    dst_size,
+                    len as u32,
+                    offset as u32,
+                    lit_len,
+                )?;
+            }
         }
     }
-    encode_literal_run(&mut outp, outp_end, dst, dst_size, lit_ptr, lit_len)?;
     /* Terminating M4 */
-    if outp.offset(3) > outp_end {
+    if outp.add(3) > outp_end {
         *dst_size = outp.offset_from(dst) as usize;
         return Err(Error::OutputOverrun);
     }
     *outp = (M4_MARKER | 1) as u8;
-    outp = outp.offset(1);
+    outp = outp.add(1);
     *outp = 0;
-    outp = outp.offset(1);
+    outp = outp.add(1);
     *outp = 0;
-    outp = outp.offset(1);
+    outp = outp.add(1);
     *dst_size = outp.offset_from(dst) as usize;
     Ok(())
 }
@@ -0,0 +1,105 @@
+//! Helpers for the "maybe-compressed arrays" of Arma's binarized P3D (ODOL)
+//! and WRP (OPRW) formats.
+//!
+//! These arrays are stored with a known element count. If the byte size of
+//! an array reaches [`THRESHOLD`], it is stored as raw LZO1X data without a
+//! length prefix; smaller arrays are stored uncompressed.
+//!
+//! ## Example
+//! ```rust
+//! use std::io::Cursor;
+//! use lzokay_native::compressed_array;
+//!
+//! fn main() -> Result<(), lzokay_native::Error> {
+//!     let normals = vec![0u8; 12 * 500];
+//!
+//!     let mut file = Vec::new();
+//!     compressed_array::write(&mut file, &normals)?;
+//!
+//!     let read = compressed_array::read(&mut Cursor::new(file), 500, 12)?;
+//!     assert_eq!(read, normals);
+//!
+//!     Ok(())
+//! }
+//! ```
+
+use std::io::{Read, Write};
+
+use crate::compress::Dict;
+use crate::decompress::{decompress_with_options, DecodeOptions};
+
+/// Arrays of at least this many bytes are stored LZO compressed.
+pub const THRESHOLD: usize = 1024;
+
+/// Reads an array of `count` elements of `element_size` bytes.
+///
+/// The reader is left positioned right after the array.
+///
+/// # Arguments
+/// * `reader` - Reader positioned at the start of the array
+/// * `count` - Number of elements in the array
+/// * `element_size` - Size of a single element in bytes
+///
+/// # Errors
+/// Will return [`Err`] if `count * element_size` overflows, there was an I/O
+/// error, the LZO data is invalid or it does not decompress to exactly
+/// `count * element_size` bytes.
+pub fn read<I>(reader: &mut I, count: usize, element_size: usize) -> Result<Vec<u8>, crate::Error>
+where
+    I: Read,
+{
+    let expected = count
+        .checked_mul(element_size)
+        .ok_or(crate::Error::InvalidHeader(
+            "compressed array size overflows",
+        ))?;
+
+    if expected < THRESHOLD {
+        let mut result = vec![0; expected];
+        reader.read_exact(&mut result)?;
+        return Ok(result);
+    }
+
+    decompress_with_options(reader, DecodeOptions::new().with_exact_size(expected))
+}
+
+/// Writes an array, LZO compressing it if it is at least [`THRESHOLD`] bytes long.
+///
+/// # Arguments
+/// * `writer` - Writer to write the array to
+/// * `data` - Raw bytes of all elements of the array
+///
+/// # Errors
+/// Will return [`Err`] if compression fails or there was an I/O error while writing to `writer`.
+pub fn write<O>(writer: &mut O, data: &[u8]) -> Result<(), crate::Error>
+where
+    O: Write,
+{
+    write_with_dict(writer, data, &mut Dict::new())
+}
+
+/// Writes an array, LZO compressing it if it is at least [`THRESHOLD`] bytes long.
+///
+/// This function differs from [`write`] in that it accepts an additional
+/// [`Dict`] argument that can be used across multiple arrays to avoid
+/// repeated allocation or deallocation of memory used by the compressor.
+///
+/// # Arguments
+/// * `writer` - Writer to write the array to
+/// * `data` - Raw bytes of all elements of the array
+/// * `dict` - Data structure to to store data in
+///
+/// # Errors
+/// See [`write`] for details on possible errors.
+pub fn write_with_dict<O>(writer: &mut O, data: &[u8], dict: &mut Dict) -> Result<(), crate::Error>
+where
+    O: Write,
+{
+    if data.len() < THRESHOLD {
+        writer.write_all(data)?;
+    } else {
+        writer.write_all(&crate::compress::compress_with_dict(data, dict)?)?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,194 @@
+//! Concatenation of raw LZO1X streams without recompression.
+//!
+//! Matches only reach into the output of their own stream, so streams can be
+//! joined by dropping the terminating M4 of each stream and copying the
+//! instructions of the following one as they are. Only the literal runs
+//! meeting at a junction are merged and re-encoded, as a stream can't hold
+//! two literal runs in a row.
+//!
+//! ## Example
+//! ```rust
+//! fn main() -> Result<(), lzokay_native::Error> {
+//!     let data = include_bytes!("../test-data/uncompressed/alice29.txt");
+//!     let first = lzokay_native::compress(&data[..1000])?;
+//!     let second = lzokay_native::compress(&data[1000..])?;
+//!
+//!     let joined = lzokay_native::concat::concat_streams(&[&first, &second])?;
+//!     assert_eq!(lzokay_native::decompress_all(&joined, None)?, data);
+//!
+//!     Ok(())
+//! }
+//! ```
+
+use std::io;
+
+use crate::decompress::{InstructionDecoder, Kind};
+
+/// Joins raw LZO1X streams into one stream decompressing to the
+/// concatenated output of all streams, and returns it as a new [`Vec<u8>`].
+///
+/// The instructions of each stream are copied verbatim, up to its last
+/// match. The literals following it are merged with the literal run starting
+/// the next stream, along with any 2-byte matches whose encoding depends on
+/// the length of that run. Streams are only decoded to validate them, their
+/// output is not kept.
+///
+/// Empty slices are treated as empty streams.
+///
+/// # Errors
+/// Will return [`Err`] if one of the streams does not decompress.
+pub fn concat_streams(streams: &[&[u8]]) -> Result<Vec<u8>, crate::Error> {
+    let mut joined = Joined {
+        out: Vec::with_capacity(streams.iter().map(|stream| stream.len()).sum()),
+        literals: Vec::new(),
+        state_pos: None,
+    };
+
+    for &stream in streams.iter().filter(|stream| !stream.is_empty()) {
+        joined.append(stream)?;
+    }
+    joined.flush_literals();
+    joined.out.extend_from_slice(&[0x11, 0, 0]);
+
+    Ok(joined.out)
+}
+
+/* The joined stream, up to the last match copied into it */
+struct Joined {
+    out: Vec<u8>,
+    /* Literals following the last match, written once the next match is known */
+    literals: Vec<u8>,
+    /* Byte holding the S bits of the last match */
+    state_pos: Option<usize>,
+}
+
+impl Joined {
+    fn append(&mut self, stream: &[u8]) -> Result<(), crate::Error> {
+        let mut reader = stream;
+        let mut decoder = InstructionDecoder::new(false);
+        let mut output = 0;
+        /* Start of the instructions copied verbatim */
+        let mut body = None;
+        /* End of the last match and the literals following it */
+        let mut last_match = None;
+        let mut trailing = 0..0;
+
+        loop {
+            let start = stream.len() - reader.len();
+            let state = decoder.state;
+            let inst = decoder.next(&mut reader)?;
+            let end = stream.len() - reader.len();
+
+            match inst.kind {
+                Kind::InitialLiterals | Kind::Literals => {
+                    let lit = take(&mut reader, inst.len)?;
+                    if body.is_none() {
+                        self.literals.extend_from_slice(lit);
+                    }
+                    trailing = end..end + inst.len;
+                    output += inst.len;
+                    continue;
+                }
+                Kind::End => {
+                    /* Ensure terminating M4 was encountered */
+                    if inst.len != 3 {
+                        return Err(crate::Error::Unknown);
+                    }
+                    break;
+                }
+                Kind::ZeroRun => return Err(crate::Error::Unknown),
+                Kind::M1 | Kind::M2 | Kind::M3 | Kind::M4 => {
+                    if inst.distance > output {
+                        return Err(crate::Error::LookbehindOverrun);
+                    }
+                    if body.is_none() {
+                        /* State the decoder is in after the pending literals */
+                        let merged = self.literals.len().min(4);
+                        if stream[start] >= 16 || class(merged) == class(state) {
+                            self.flush_literals();
+                            body = Some(start);
+                        } else {
+                            /* The merged literal run changed the meaning of the M1
+                             * encoding. The output of the stream so far ends the
+                             * pending literals, so the match is copied from there.
+                             */
+                            let from = self.literals.len() - inst.distance;
+                            for i in from..from + inst.len {
+                                self.literals.push(self.literals[i]);
+                            }
+                        }
+                    }
+                    output += inst.len;
+                }
+            }
+
+            /* Copy literal */
+            let lit = take(&mut reader, inst.state)?;
+            if body.is_none() {
+                self.literals.extend_from_slice(lit);
+            }
+            last_match = Some(end);
+            trailing = end..end + inst.state;
+            output += inst.state;
+        }
+
+        if let (Some(body), Some(last_match)) = (body, last_match) {
+            self.out.extend_from_slice(&stream[body..last_match]);
+            /* The literals following the last match move to the next junction */
+            let pos = self.out.len() - 2;
+            self.out[pos] &= !0x3;
+            self.state_pos = Some(pos);
+            self.literals = stream[trailing].to_vec();
+        }
+
+        Ok(())
+    }
+
+    fn flush_literals(&mut self) {
+        let len = self.literals.len();
+        if len == 0 {
+            return;
+        }
+
+        if self.out.is_empty() && len <= 238 {
+            self.out.push((17 + len) as u8);
+        } else if len <= 3 {
+            if let Some(pos) = self.state_pos {
+                self.out[pos] |= len as u8;
+            }
+        } else if len <= 18 {
+            self.out.push((len - 3) as u8);
+        } else {
+            self.out.push(0);
+            /* (zero_bytes * 255) + non_zero_byte */
+            let mut rest = len - 18;
+            while rest > 255 {
+                self.out.push(0);
+                rest -= 255;
+            }
+            self.out.push(rest as u8);
+        }
+        self.out.append(&mut self.literals);
+    }
+}
+
+/* Instructions below 16 read as literal runs in state 0, 2-byte matches in
+ * states 1..3 and 3-byte matches in state 4.
+ */
+const fn class(state: usize) -> usize {
+    match state {
+        0 => 0,
+        1..=3 => 1,
+        _ => 2,
+    }
+}
+
+/* Skips `len` literals of `reader` and returns them */
+fn take<'a>(reader: &mut &'a [u8], len: usize) -> Result<&'a [u8], crate::Error> {
+    if reader.len() < len {
+        return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into());
+    }
+    let (lit, rest) = reader.split_at(len);
+    *reader = rest;
+    Ok(lit)
+}
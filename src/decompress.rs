@@ -1,66 +1,117 @@
-use std::io::{Read, Seek, Write};
+use std::io::{self, Read, Seek};
 
-use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use byteorder::{LittleEndian, ReadBytesExt};
 
-use crate::util::{consume_zero_byte_length_stream, peek_u8, read_bytes, M3_MARKER, M4_MARKER};
+use crate::util::{M3_MARKER, M4_MARKER};
 
-/// Decompresses a lzo-compressed reader and returns the result as a new [`Vec<u8>`].
-///
-/// # Arguments
-/// * `reader` - Reader with compressed data
-/// * `expected_size` - Expected size of output. This is used to allocate the right amount of memory from the start.
-///
-/// # Errors
-/// Will return [`Err`] if there was any kind of I/O error while reading from `reader` or if an unexpected byte is encountered.
-///
-/// # Example
-/// ```rust
-/// # use std::fs::File;
-/// # let file_path = "./test-data/compressed/fields.c.lzo";
-/// let mut file = File::open(file_path).unwrap(); // file implements std::io::Read
-///
-/// let decompressed = lzokay_native::decompress(&mut file, None);
-/// ```
-///
-pub fn decompress<I>(reader: &mut I, expected_size: Option<usize>) -> Result<Vec<u8>, crate::Error>
-where
-    I: Read + Seek,
-{
-    let mut result = Vec::<u8>::with_capacity(expected_size.unwrap_or_default());
+/// Minimum length of a zero run instruction of the `lzo-rle` bitstream.
+#[allow(clippy::redundant_pub_crate)]
+pub(crate) const MIN_ZERO_RUN_LENGTH: usize = 4;
+
+/// Maximum length of a zero run instruction of the `lzo-rle` bitstream.
+#[cfg(feature = "lzo-rle")]
+#[allow(clippy::redundant_pub_crate)]
+pub(crate) const MAX_ZERO_RUN_LENGTH: usize = 2047 + MIN_ZERO_RUN_LENGTH;
+
+/* Upper bound of the output preallocated for an untrusted exact size */
+const MAX_PREALLOCATION: usize = 1 << 20;
+
+/* Kind of a single instruction of a LZO1X stream */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[allow(clippy::redundant_pub_crate)]
+pub(crate) enum Kind {
+    InitialLiterals,
+    Literals,
+    M1,
+    M2,
+    M3,
+    M4,
+    ZeroRun,
+    End,
+}
+
+/* A decoded instruction. For matches, `state` literals follow the instruction. */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[allow(clippy::redundant_pub_crate)]
+pub(crate) struct RawInstruction {
+    pub kind: Kind,
+    pub len: usize,
+    pub distance: usize,
+    pub state: usize,
+}
+
+/* Splits a LZO1X stream into instructions, without touching literal data */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[allow(clippy::redundant_pub_crate)]
+pub(crate) struct InstructionDecoder {
+    pub state: usize,
+    pub first: bool,
+    pub rle: bool,
+    /* Whether the end of the input at an instruction boundary ends the stream */
+    pub eof_end: bool,
+}
+
+impl InstructionDecoder {
+    pub(crate) const fn new(rle: bool) -> Self {
+        Self {
+            state: 0,
+            first: true,
+            rle,
+            eof_end: false,
+        }
+    }
 
-    let mut lbcur: u64;
-    let mut lblen: usize;
-    let mut state: usize = 0;
-    let mut n_state: usize;
-
-    /* First byte encoding */
-    if peek_u8(reader)? >= 22 {
-        /* 22..255 : copy literal string
-         *           length = (byte - 17) = 4..238
-         *           state = 4 [ don't copy extra literals ]
-         *           skip byte
-         */
-        let len: usize = (reader.read_u8()? - 17) as usize;
-        result.write_all(&read_bytes(reader, len)?)?;
-        state = 4;
-    } else if peek_u8(reader)? >= 18 {
-        /* 18..21 : copy 0..3 literals
-         *          state = (byte - 17) = 0..3  [ copy <state> literals ]
-         *          skip byte
-         */
-        n_state = (reader.read_u8()? - 17) as usize;
-        state = n_state;
-        result.write_all(&read_bytes(reader, n_state)?)?;
-    }
-    loop
-    /* 0..17 : follow regular instruction encoding, see below. It is worth
-     *         noting that codes 16 and 17 will represent a block copy from
-     *         the dictionary which is empty, and that they will always be
-     *         invalid at this place.
-     */
+    /* Reads the next instruction. Literal data of the instruction is left in `reader`. */
+    #[allow(clippy::too_many_lines)]
+    pub(crate) fn next<I>(&mut self, reader: &mut I) -> Result<RawInstruction, crate::Error>
+    where
+        I: Read,
     {
-        let inst = reader.read_u8()?;
-        if (u32::from(inst) & 0xc0) != 0 {
+        let mut inst = [0];
+        if self.eof_end {
+            if reader.read(&mut inst)? == 0 {
+                return Ok(RawInstruction {
+                    kind: Kind::End,
+                    len: 3,
+                    distance: 16384,
+                    state: 0,
+                });
+            }
+        } else {
+            reader.read_exact(&mut inst)?;
+        }
+        let [inst] = inst;
+
+        if self.first {
+            self.first = false;
+
+            /* First byte encoding */
+            if inst >= 18 {
+                /* 22..255 : copy literal string
+                 *           length = (byte - 17) = 4..238
+                 *           state = 4 [ don't copy extra literals ]
+                 *           skip byte
+                 * 18..21 : copy 1..4 literals
+                 *          state = (byte - 17) = 1..4  [ copy <state> literals ]
+                 *          skip byte
+                 */
+                let len = (inst - 17) as usize;
+                self.state = len.min(4);
+                return Ok(RawInstruction {
+                    kind: Kind::InitialLiterals,
+                    len,
+                    distance: 0,
+                    state: self.state,
+                });
+            }
+            /* 0..17 : follow regular instruction encoding, see below. It is worth
+             *         noting that codes 16 and 17 will represent a block copy from
+             *         the dictionary which is empty, and that they will always be
+             *         invalid at this place.
+             */
+        }
+
+        let raw = if (u32::from(inst) & 0xc0) != 0 {
             /* [M2]
              * 1 L L D D D S S  (128..255)
              *   Copy 5-8 bytes from block within 2kB distance
@@ -76,12 +127,12 @@ where
              * Always followed by exactly one byte : H H H H H H H H
              *   distance = (H << 3) + D + 1
              */
-            lbcur = result.len() as u64
-                - u64::from(
-                    (u32::from(reader.read_u8()?) << 3) + ((u32::from(inst) >> 2) & 0x7) + 1,
-                );
-            lblen = ((inst >> 5) as usize) + 1;
-            n_state = (inst & 0x3) as usize;
+            RawInstruction {
+                kind: Kind::M2,
+                len: ((inst >> 5) as usize) + 1,
+                distance: ((reader.read_u8()? as usize) << 3) + ((inst as usize >> 2) & 0x7) + 1,
+                state: (inst & 0x3) as usize,
+            }
         } else if (u32::from(inst) & M3_MARKER) != 0 {
             /* [M3]
              * 0 0 1 L L L L L  (32..63)
@@ -91,41 +142,46 @@ where
              *   distance = D + 1
              *   state = S (copy S literals after this block)
              */
-            lblen = ((inst & 0x1f) as usize).wrapping_add(2);
-            if lblen == 2 {
-                let offset = consume_zero_byte_length_stream(reader)?;
-                lblen += (offset * 255 + 31 + u64::from(reader.read_u8()?)) as usize;
+            let mut len = (inst & 0x1f) as usize;
+            if len == 0 {
+                len = read_length(reader, 31)?;
             }
-            n_state = reader.read_u16::<LittleEndian>()? as usize;
-            lbcur = result.len() as u64 - ((n_state >> 2).wrapping_add(1) as u64);
-            n_state &= 0x3;
-        } else if u32::from(inst) & M4_MARKER != 0 {
-            /* [M4]
-             * 0 0 0 1 H L L L  (16..31)
-             *   Copy of a block within 16..48kB distance (preferably less than 10B)
-             *   length = 2 + (L ?: 7 + (zero_bytes * 255) + non_zero_byte)
-             * Always followed by exactly one LE16 :  D D D D D D D D : D D D D D D S S
-             *   distance = 16384 + (H << 14) + D
-             *   state = S (copy S literals after this block)
-             *   End of stream is reached if distance == 16384
-             */
-            lblen = ((inst & 0x7) as usize).wrapping_add(2); /* Stream finished */
-            if lblen == 2 {
-                let offset = consume_zero_byte_length_stream(reader)?;
-                lblen += (offset * 255 + 7 + u64::from(reader.read_u8()?)) as usize;
+            let n_state = reader.read_u16::<LittleEndian>()? as usize;
+            RawInstruction {
+                kind: Kind::M3,
+                len: len + 2,
+                distance: (n_state >> 2) + 1,
+                state: n_state & 0x3,
             }
-            n_state = reader.read_u16::<LittleEndian>()? as usize;
-
-            lbcur = (result.len() as u64).wrapping_sub(
-                ((i32::from(inst & 0x8) << 11) as u64).wrapping_add((n_state >> 2_usize) as u64),
-            );
-
-            n_state &= 0x3;
-            if lbcur == result.len() as u64 {
-                break;
+        } else if u32::from(inst) & M4_MARKER != 0 {
+            if self.rle && (inst & 0xf8) == 0x18 {
+                /* [Zero run] (lzo-rle only)
+                 * 0 0 0 1 1 R R R  (24..31)
+                 * Followed by exactly one LE16 : 1 1 1 1 1 1 1 1 : 1 1 1 1 1 1 S S
+                 * and one byte : R R R R R R R R
+                 *   length = 4 + (R R R R R R R R R R R)
+                 *   state = S (copy S literals after this block)
+                 * Any other LE16 is a regular M4 with H = 1.
+                 */
+                let mut next = [0u8; 2];
+                reader.read_exact(&mut next)?;
+                let n_state = u16::from_le_bytes(next) as usize;
+                if n_state & 0xfffc == 0xfffc {
+                    let len = ((inst & 0x7) as usize | (reader.read_u8()? as usize) << 3)
+                        + MIN_ZERO_RUN_LENGTH;
+                    RawInstruction {
+                        kind: Kind::ZeroRun,
+                        len,
+                        distance: 0,
+                        state: n_state & 0x3,
+                    }
+                } else {
+                    read_m4(&mut next.as_slice().chain(reader), inst)?
+                }
+            } else {
+                read_m4(reader, inst)?
             }
-            lbcur -= 16384;
-        } else if state == 0 {
+        } else if self.state == 0 {
             /* [M1] Depends on the number of literals copied by the last instruction. */
             /* If last instruction did not copy any literal (state == 0), this
              * encoding will be a copy of 4 or more literal, and must be interpreted
@@ -135,16 +191,17 @@ where
              *    length = 3 + (L ?: 15 + (zero_bytes * 255) + non_zero_byte)
              *    state = 4  (no extra literals are copied)
              */
-            let mut len: usize = (inst + 3) as usize;
-            if len == 3 {
-                let offset = consume_zero_byte_length_stream(reader)?;
-                len += (offset * 255 + 15 + u64::from(reader.read_u8()?)) as usize;
+            let mut len = inst as usize;
+            if len == 0 {
+                len = read_length(reader, 15)?;
             }
-            /* copy_literal_run */
-            result.write_all(&read_bytes(reader, len)?)?;
-            state = 4;
-            continue;
-        } else if state != 4 {
+            RawInstruction {
+                kind: Kind::Literals,
+                len: len + 3,
+                distance: 0,
+                state: 4,
+            }
+        } else if self.state != 4 {
             /* If last instruction used to copy between 1 to 3 literals (encoded in
              * the instruction's opcode or distance), the instruction is a copy of a
              * 2-byte block from the dictionary within a 1kB distance. It is worth
@@ -158,13 +215,12 @@ where
              *  Always followed by exactly one byte : H H H H H H H H
              *    distance = (H << 2) + D + 1
              */
-            n_state = (u32::from(inst) & 0x3) as usize;
-
-            lbcur = (result.len() as u64).wrapping_sub(u64::from(
-                (u32::from(inst) >> 2)
-                    .wrapping_add((u32::from(reader.read_u8()?) << 2).wrapping_add(1)),
-            ));
-            lblen = 2;
+            RawInstruction {
+                kind: Kind::M1,
+                len: 2,
+                distance: (inst as usize >> 2) + ((reader.read_u8()? as usize) << 2) + 1,
+                state: (inst & 0x3) as usize,
+            }
         } else {
             /* If last instruction used to copy 4 or more literals (as detected by
              * state == 4), the instruction becomes a copy of a 3-byte block from the
@@ -176,31 +232,286 @@ where
              *  Always followed by exactly one byte : H H H H H H H H
              *    distance = (H << 2) + D + 2049
              */
-            n_state = (inst & 0x3) as usize;
-            lbcur = (result.len() as u64)
-                - (((u32::from(inst) >> 2) + (u32::from(reader.read_u8()?) << 2) + 2049) as isize)
-                    as u64;
-            lblen = 3;
+            RawInstruction {
+                kind: Kind::M1,
+                len: 3,
+                distance: (inst as usize >> 2) + ((reader.read_u8()? as usize) << 2) + 2049,
+                state: (inst & 0x3) as usize,
+            }
+        };
+
+        self.state = raw.state;
+        Ok(raw)
+    }
+}
+
+/* [M4]
+ * 0 0 0 1 H L L L  (16..31)
+ *   Copy of a block within 16..48kB distance (preferably less than 10B)
+ *   length = 2 + (L ?: 7 + (zero_bytes * 255) + non_zero_byte)
+ * Always followed by exactly one LE16 :  D D D D D D D D : D D D D D D S S
+ *   distance = 16384 + (H << 14) + D
+ *   state = S (copy S literals after this block)
+ *   End of stream is reached if distance == 16384
+ */
+fn read_m4<I>(reader: &mut I, inst: u8) -> Result<RawInstruction, crate::Error>
+where
+    I: Read,
+{
+    let mut len = (inst & 0x7) as usize;
+    if len == 0 {
+        len = read_length(reader, 7)?;
+    }
+    let n_state = reader.read_u16::<LittleEndian>()? as usize;
+    let distance = (((inst & 0x8) as usize) << 11) + (n_state >> 2);
+
+    Ok(RawInstruction {
+        kind: if distance == 0 { Kind::End } else { Kind::M4 },
+        len: len + 2,
+        distance: distance + 16384,
+        state: n_state & 0x3,
+    })
+}
+
+/* Reads the extended length following an instruction with a zero length field:
+ * (zero_bytes * 255) + non_zero_byte + base
+ */
+fn read_length<I>(reader: &mut I, base: usize) -> Result<usize, crate::Error>
+where
+    I: Read,
+{
+    let mut len = base;
+    loop {
+        match reader.read_u8()? {
+            0 => len += 255,
+            b => return Ok(len + b as usize),
         }
+    }
+}
+
+/* Destination of the output of a decoded stream */
+#[allow(clippy::redundant_pub_crate)]
+pub(crate) trait Sink {
+    /* Number of bytes written so far */
+    #[cfg(any(feature = "checkpoint", feature = "in-place"))]
+    fn len(&self) -> usize;
+
+    /* Copies `len` literals from `reader` */
+    fn literals<I>(&mut self, reader: &mut I, len: usize) -> Result<(), crate::Error>
+    where
+        I: Read;
+
+    /* Copies a block of `len` bytes from `distance` bytes back */
+    fn lookback(&mut self, distance: usize, len: usize) -> Result<(), crate::Error>;
+
+    /* Writes `len` zero bytes */
+    fn zeros(&mut self, len: usize) -> Result<(), crate::Error>;
+}
+
+impl Sink for Vec<u8> {
+    #[cfg(any(feature = "checkpoint", feature = "in-place"))]
+    fn len(&self) -> usize {
+        self.len()
+    }
 
-        for i in 0..lblen {
-            let val = result[lbcur as usize + i];
-            result.write_u8(val)?;
+    fn literals<I>(&mut self, reader: &mut I, len: usize) -> Result<(), crate::Error>
+    where
+        I: Read,
+    {
+        if (&mut *reader).take(len as u64).read_to_end(self)? != len {
+            return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into());
         }
+        Ok(())
+    }
 
-        state = n_state;
+    fn lookback(&mut self, distance: usize, len: usize) -> Result<(), crate::Error> {
+        if distance == 0 || distance > self.len() {
+            return Err(crate::Error::LookbehindOverrun);
+        }
 
-        /* Copy literal */
+        let start = self.len() - distance;
+        if distance >= len {
+            self.extend_from_within(start..start + len);
+        } else {
+            for i in 0..len {
+                let val = self[start + i];
+                self.push(val);
+            }
+        }
+        Ok(())
+    }
+
+    fn zeros(&mut self, len: usize) -> Result<(), crate::Error> {
+        self.resize(self.len() + len, 0);
+        Ok(())
+    }
+}
+
+/* Sink that only counts the output, for decoding a stream without keeping it */
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[allow(clippy::redundant_pub_crate)]
+pub(crate) struct Counter(pub usize);
+
+impl Sink for Counter {
+    #[cfg(any(feature = "checkpoint", feature = "in-place"))]
+    fn len(&self) -> usize {
+        self.0
+    }
+
+    fn literals<I>(&mut self, reader: &mut I, len: usize) -> Result<(), crate::Error>
+    where
+        I: Read,
+    {
+        if io::copy(&mut (&mut *reader).take(len as u64), &mut io::sink())? != len as u64 {
+            return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into());
+        }
+        self.0 += len;
+        Ok(())
+    }
+
+    fn lookback(&mut self, distance: usize, len: usize) -> Result<(), crate::Error> {
+        if distance == 0 || distance > self.0 {
+            return Err(crate::Error::LookbehindOverrun);
+        }
+        self.0 += len;
+        Ok(())
+    }
+
+    fn zeros(&mut self, len: usize) -> Result<(), crate::Error> {
+        self.0 += len;
+        Ok(())
+    }
+}
 
-        result.write_all(&read_bytes(reader, n_state)?)?;
+/* Sink refusing to grow past the exact size the output is expected to have */
+struct Exact<'a> {
+    output: &'a mut Vec<u8>,
+    size: usize,
+}
+
+impl Exact<'_> {
+    fn check(&self, len: usize) -> Result<(), crate::Error> {
+        if self.output.len() + len > self.size {
+            return Err(crate::Error::SizeMismatch {
+                expected: self.size,
+                actual: self.output.len() + len,
+            });
+        }
+        Ok(())
     }
-    // *dst_size = outp.offset_from(dst) as usize;
-    if lblen != 3 {
-        /* Ensure terminating M4 was encountered */
-        return Err(crate::Error::Unknown);
+}
+
+impl Sink for Exact<'_> {
+    #[cfg(any(feature = "checkpoint", feature = "in-place"))]
+    fn len(&self) -> usize {
+        self.output.len()
     }
 
-    result.flush()?;
+    fn literals<I>(&mut self, reader: &mut I, len: usize) -> Result<(), crate::Error>
+    where
+        I: Read,
+    {
+        self.check(len)?;
+        self.output.literals(reader, len)
+    }
+
+    fn lookback(&mut self, distance: usize, len: usize) -> Result<(), crate::Error> {
+        self.check(len)?;
+        self.output.lookback(distance, len)
+    }
+
+    fn zeros(&mut self, len: usize) -> Result<(), crate::Error> {
+        self.check(len)?;
+        self.output.zeros(len)
+    }
+}
+
+/* Decodes instructions from `reader` into `sink` until the end of the stream
+ * or until `stop` returns `true` at an instruction boundary. Returns whether
+ * the end of the stream was reached.
+ */
+#[allow(clippy::redundant_pub_crate)]
+pub(crate) fn decode<I, S, F>(
+    reader: &mut I,
+    decoder: &mut InstructionDecoder,
+    sink: &mut S,
+    mut stop: F,
+) -> Result<bool, crate::Error>
+where
+    I: Read,
+    S: Sink,
+    F: FnMut(&I, &S) -> bool,
+{
+    loop {
+        if stop(reader, sink) {
+            return Ok(false);
+        }
+
+        let inst = decoder.next(reader)?;
+        match inst.kind {
+            Kind::InitialLiterals | Kind::Literals => {
+                sink.literals(reader, inst.len)?;
+                continue;
+            }
+            Kind::End => {
+                /* Ensure terminating M4 was encountered */
+                if inst.len != 3 {
+                    return Err(crate::Error::Unknown);
+                }
+                return Ok(true);
+            }
+            Kind::ZeroRun => sink.zeros(inst.len)?,
+            Kind::M1 | Kind::M2 | Kind::M3 | Kind::M4 => sink.lookback(inst.distance, inst.len)?,
+        }
+
+        /* Copy literal */
+        sink.literals(reader, inst.state)?;
+    }
+}
+
+/* Decompresses a whole stream from `reader` into `sink` */
+#[allow(clippy::redundant_pub_crate)]
+pub(crate) fn decompress_into<I, S>(
+    reader: &mut I,
+    sink: &mut S,
+    rle: bool,
+) -> Result<(), crate::Error>
+where
+    I: Read,
+    S: Sink,
+{
+    decode(reader, &mut InstructionDecoder::new(rle), sink, |_, _| {
+        false
+    })?;
+    Ok(())
+}
+
+/// Decompresses a lzo-compressed reader and returns the result as a new [`Vec<u8>`].
+///
+/// # Arguments
+/// * `reader` - Reader with compressed data
+/// * `expected_size` - Expected size of output. This is used to allocate the right amount of memory from the start.
+///   Use [`DecodeOptions::with_exact_size`] if the output must have exactly this size.
+///
+/// # Errors
+/// Will return [`Err`] if there was any kind of I/O error while reading from `reader` or if an unexpected byte is encountered.
+///
+/// # Example
+/// ```rust
+/// # use std::fs::File;
+/// # let file_path = "./test-data/compressed/fields.c.lzo";
+/// let mut file = File::open(file_path).unwrap(); // file implements std::io::Read
+///
+/// let decompressed = lzokay_native::decompress(&mut file, None);
+/// ```
+///
+pub fn decompress<I>(reader: &mut I, expected_size: Option<usize>) -> Result<Vec<u8>, crate::Error>
+where
+    I: Read + Seek,
+{
+    let mut result = Vec::<u8>::with_capacity(expected_size.unwrap_or_default());
+
+    decompress_into(reader, &mut result, false)?;
 
     Ok(result)
 }
@@ -210,6 +521,7 @@ where
 /// # Arguments
 /// * `data` - Data to decompress
 /// * `expected_size` - Expected size of output. This is used to allocate the right amount of memory from the start.
+///   Use [`DecodeOptions::with_exact_size`] if the output must have exactly this size.
 ///
 /// # Errors
 /// See [`decompress`] for details on possible errors.
@@ -226,3 +538,320 @@ pub fn decompress_all(data: &[u8], expected_size: Option<usize>) -> Result<Vec<u
 
     decompress(&mut data_reader, expected_size)
 }
+
+/// Result of checking a stream with [`validate`].
+#[derive(Debug)]
+pub struct Validation {
+    /// Number of bytes the stream decompresses to, or decoded before the error
+    pub output_len: usize,
+    /// Number of bytes read from the reader, including the terminating M4
+    pub input_len: u64,
+    /// First error encountered, [`None`] if the stream is well-formed
+    pub error: Option<crate::Error>,
+}
+
+impl Validation {
+    /// Returns `true` if the stream is well-formed.
+    #[must_use]
+    pub const fn is_valid(&self) -> bool {
+        self.error.is_none()
+    }
+}
+
+/// Checks whether a reader holds a well-formed LZO1X stream, without keeping
+/// the output.
+///
+/// The stream is decoded with the same logic as [`decompress`], but only the
+/// size of the output is tracked, so memory use does not depend on the stream.
+///
+/// # Arguments
+/// * `reader` - Reader with compressed data
+///
+/// # Example
+/// ```rust
+/// let data = include_bytes!("../test-data/compressed/fields.c.lzo");
+///
+/// let validation = lzokay_native::validate(&mut data.as_slice());
+/// assert!(validation.is_valid());
+/// assert_eq!(validation.input_len, data.len() as u64);
+/// ```
+///
+pub fn validate<I>(reader: &mut I) -> Validation
+where
+    I: Read,
+{
+    let mut reader = CountingReader {
+        inner: reader,
+        count: 0,
+    };
+    let mut output = Counter::default();
+    let error = decompress_into(&mut reader, &mut output, false).err();
+
+    Validation {
+        output_len: output.0,
+        input_len: reader.count,
+        error,
+    }
+}
+
+/// Returns the size a byte slice decompresses to, without keeping the output.
+///
+/// # Arguments
+/// * `data` - Data to decompress
+///
+/// # Errors
+/// See [`decompress`] for details on possible errors.
+///
+/// # Example
+/// ```rust
+/// let data = include_bytes!("../test-data/compressed/fields.c.lzo");
+///
+/// let size = lzokay_native::decompressed_size(data).unwrap();
+/// ```
+///
+pub fn decompressed_size(data: &[u8]) -> Result<usize, crate::Error> {
+    let mut output = Counter::default();
+    decompress_into(&mut std::io::Cursor::new(data), &mut output, false)?;
+    Ok(output.0)
+}
+
+/// Output recovered from a stream by [`decompress_partial`].
+#[derive(Debug)]
+pub struct PartialOutput {
+    /// Output of the instructions decoded before the error
+    pub output: Vec<u8>,
+    /// Offset of the instruction that failed, or the number of bytes read
+    /// if the stream is well-formed
+    pub input_offset: u64,
+    /// Error that stopped decoding, [`None`] if the stream is well-formed
+    pub error: Option<crate::Error>,
+}
+
+/// Decompresses a lzo-compressed reader, keeping the output decoded before an error.
+///
+/// Unlike [`decompress`], the output of the instructions preceding a corrupt
+/// one is returned together with the error and the offset of the failing
+/// instruction in the input.
+///
+/// # Arguments
+/// * `reader` - Reader with compressed data
+///
+/// # Example
+/// ```rust
+/// let data = include_bytes!("../test-data/compressed/fields.c.lzo");
+///
+/// let partial = lzokay_native::decompress_partial(&mut &data[..data.len() / 2]);
+/// assert!(partial.error.is_some());
+/// assert!(!partial.output.is_empty());
+/// ```
+///
+pub fn decompress_partial<I>(reader: &mut I) -> PartialOutput
+where
+    I: Read,
+{
+    let mut reader = CountingReader {
+        inner: reader,
+        count: 0,
+    };
+    let mut output = Vec::new();
+    let mut boundary = (0, 0);
+    let result = decode(
+        &mut reader,
+        &mut InstructionDecoder::new(false),
+        &mut output,
+        |reader, output| {
+            boundary = (reader.count, output.len());
+            false
+        },
+    );
+
+    match result {
+        Ok(_) => PartialOutput {
+            output,
+            input_offset: reader.count,
+            error: None,
+        },
+        Err(err) => {
+            /* Drop what the failing instruction produced */
+            output.truncate(boundary.1);
+            PartialOutput {
+                output,
+                input_offset: boundary.0,
+                error: Some(err),
+            }
+        }
+    }
+}
+
+/* Reader counting the bytes read through it */
+struct CountingReader<I> {
+    inner: I,
+    count: u64,
+}
+
+impl<I> Read for CountingReader<I>
+where
+    I: Read,
+{
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let len = self.inner.read(buf)?;
+        self.count += len as u64;
+        Ok(len)
+    }
+}
+
+/// How the end of a stream is detected by [`decompress_with_options`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum EndOfStream {
+    /// The stream must end with the terminating M4
+    #[default]
+    Terminator,
+    /// The end of the input at an instruction boundary also ends the stream
+    TerminatorOrEof,
+}
+
+/// Options of [`decompress_with_options`]. The default options decode like [`decompress`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct DecodeOptions {
+    /// How the end of the stream is detected
+    pub end: EndOfStream,
+    /// Stops decoding once this many bytes are produced and truncates the
+    /// output to it
+    pub stop_at: Option<usize>,
+    /// Whether bytes following the end of the stream are an error
+    pub reject_trailing: bool,
+    /// Size the output must have. Decoding fails as soon as the output would
+    /// grow past it, or if the stream ends short of it.
+    pub exact_size: Option<usize>,
+}
+
+impl DecodeOptions {
+    /// Creates options requiring the terminating M4 and ignoring trailing data.
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            end: EndOfStream::Terminator,
+            stop_at: None,
+            reject_trailing: false,
+            exact_size: None,
+        }
+    }
+
+    /// Returns the options with the end of the stream detected as `end`.
+    #[must_use]
+    pub const fn with_end(mut self, end: EndOfStream) -> Self {
+        self.end = end;
+        self
+    }
+
+    /// Returns the options stopping once `size` bytes are produced.
+    #[must_use]
+    pub const fn with_stop_at(mut self, size: usize) -> Self {
+        self.stop_at = Some(size);
+        self
+    }
+
+    /// Returns the options requiring the output to be exactly `size` bytes.
+    #[must_use]
+    pub const fn with_exact_size(mut self, size: usize) -> Self {
+        self.exact_size = Some(size);
+        self
+    }
+
+    /// Returns the options rejecting bytes following the end of the stream.
+    #[must_use]
+    pub const fn with_reject_trailing(mut self) -> Self {
+        self.reject_trailing = true;
+        self
+    }
+}
+
+/// Decompresses a lzo-compressed reader with the given options and returns
+/// the result as a new [`Vec<u8>`].
+///
+/// Trailing data is only checked if the end of the stream was reached, not
+/// if decoding stopped at [`DecodeOptions::stop_at`].
+///
+/// # Arguments
+/// * `reader` - Reader with compressed data
+/// * `options` - Options of the decoder
+///
+/// # Errors
+/// See [`decompress`] for details on possible errors. Will also return
+/// [`Error::TrailingData`](crate::Error::TrailingData) if trailing data is rejected and found,
+/// and [`Error::SizeMismatch`](crate::Error::SizeMismatch) if an exact size is required and
+/// the stream produces fewer or more bytes.
+///
+/// # Example
+/// ```rust
+/// use lzokay_native::{DecodeOptions, EndOfStream};
+///
+/// let data = include_bytes!("../test-data/compressed/fields.c.lzo");
+/// let options = DecodeOptions::new()
+///     .with_end(EndOfStream::TerminatorOrEof)
+///     .with_reject_trailing();
+///
+/// let decompressed = lzokay_native::decompress_with_options(&mut data.as_slice(), options);
+/// ```
+///
+pub fn decompress_with_options<I>(
+    reader: &mut I,
+    options: DecodeOptions,
+) -> Result<Vec<u8>, crate::Error>
+where
+    I: Read,
+{
+    let mut result = Vec::with_capacity(
+        options
+            .exact_size
+            .unwrap_or_default()
+            .min(MAX_PREALLOCATION),
+    );
+    let mut decoder = InstructionDecoder::new(false);
+    decoder.eof_end = options.end == EndOfStream::TerminatorOrEof;
+    let stop_at = options.stop_at.unwrap_or(usize::MAX);
+
+    let ended = if let Some(size) = options.exact_size {
+        let mut sink = Exact {
+            output: &mut result,
+            size,
+        };
+        decode(reader, &mut decoder, &mut sink, |_, sink| {
+            sink.output.len() >= stop_at
+        })?
+    } else {
+        decode(reader, &mut decoder, &mut result, |_, output| {
+            output.len() >= stop_at
+        })?
+    };
+    result.truncate(stop_at);
+    if let Some(size) = options.exact_size {
+        if result.len() != size {
+            return Err(crate::Error::SizeMismatch {
+                expected: size,
+                actual: result.len(),
+            });
+        }
+    }
+    if ended && options.reject_trailing && reader.read(&mut [0])? != 0 {
+        return Err(crate::Error::TrailingData);
+    }
+
+    Ok(result)
+}
+
+/// Decompresses a byte slice with the given options and returns the result as
+/// a new [`Vec<u8>`].
+///
+/// # Arguments
+/// * `data` - Data to decompress
+/// * `options` - Options of the decoder
+///
+/// # Errors
+/// See [`decompress_with_options`] for details on possible errors.
+pub fn decompress_all_with_options(
+    data: &[u8],
+    options: DecodeOptions,
+) -> Result<Vec<u8>, crate::Error> {
+    decompress_with_options(&mut std::io::Cursor::new(data), options)
+}
@@ -0,0 +1,78 @@
+//! Detection of the format of LZO compressed data.
+//!
+//! [`detect`] recognizes lzop files by their magic and `python-lzo` headers
+//! by their method byte, if the data following the header decompresses to
+//! the stored length. Anything else that decompresses is a raw LZO1X stream.
+//! Data is decompressed without keeping the output to check this.
+//!
+//! ## Example
+//! ```rust
+//! use lzokay_native::detect::{self, Format};
+//!
+//! fn main() -> Result<(), lzokay_native::Error> {
+//!     let data = include_bytes!("../test-data/compressed/fields.c.lzo");
+//!
+//!     assert_eq!(detect::detect(data), Some(Format::Raw));
+//!     let decompressed = detect::decompress_auto(data)?;
+//!
+//!     Ok(())
+//! }
+//! ```
+
+use std::io::Cursor;
+
+use byteorder::{BigEndian, ByteOrder};
+
+use crate::decompress::{decompress_into, Counter};
+use crate::python_lzo::{LZO1X_1, LZO1X_999};
+
+/// Format of LZO compressed data.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    /// lzop file, see [`crate::lzop`]
+    Lzop,
+    /// Data with a `python-lzo` header, see [`crate::python_lzo`]
+    PythonLzo,
+    /// Raw LZO1X stream
+    Raw,
+}
+
+/// Detects the format of LZO compressed data.
+///
+/// Returns [`None`] if the data is neither an lzop file nor decompresses.
+#[must_use]
+pub fn detect(data: &[u8]) -> Option<Format> {
+    if data.starts_with(&crate::lzop::MAGIC) {
+        return Some(Format::Lzop);
+    }
+
+    if data.len() >= 5 && matches!(data[0], LZO1X_1 | LZO1X_999) {
+        let len = BigEndian::read_u32(&data[1..]) as usize;
+        if dry_run(&data[5..]) == Some(len) {
+            return Some(Format::PythonLzo);
+        }
+    }
+
+    dry_run(data).map(|_| Format::Raw)
+}
+
+/// Detects the format of LZO compressed data and decompresses it accordingly,
+/// returning the result as a new [`Vec<u8>`].
+///
+/// # Errors
+/// Will return [`Err`] if the format is not detected or decompression fails.
+pub fn decompress_auto(data: &[u8]) -> Result<Vec<u8>, crate::Error> {
+    match detect(data) {
+        Some(Format::Lzop) => crate::lzop::decompress(&mut Cursor::new(data)),
+        Some(Format::PythonLzo) => crate::python_lzo::decompress(data),
+        Some(Format::Raw) => crate::decompress::decompress_all(data, None),
+        None => Err(crate::Error::InvalidHeader("unknown LZO format")),
+    }
+}
+
+/* Decompresses a raw stream without keeping the output, returning its size */
+fn dry_run(data: &[u8]) -> Option<usize> {
+    let mut counter = Counter::default();
+    decompress_into(&mut Cursor::new(data), &mut counter, false).ok()?;
+    Some(counter.0)
+}
@@ -0,0 +1,170 @@
+//! Instruction-level disassembly of raw LZO1X streams.
+//!
+//! [`disassemble`] returns an iterator over the instructions of a stream,
+//! decoded with the same logic as [`decompress`](crate::decompress). Literal
+//! data is skipped, so no output is kept.
+//!
+//! ## Example
+//! ```rust
+//! use lzokay_native::disasm::{self, Instruction};
+//!
+//! fn main() -> Result<(), lzokay_native::Error> {
+//!     let data = include_bytes!("../test-data/uncompressed/alice29.txt");
+//!     let compressed = lzokay_native::compress(data)?;
+//!
+//!     let mut matches = 0;
+//!     for decoded in disasm::disassemble(&compressed) {
+//!         let decoded = decoded?;
+//!         if matches!(decoded.instruction, Instruction::M2 | Instruction::M3) {
+//!             matches += 1;
+//!         }
+//!         println!(
+//!             "{:8} {:8} {:?} len={} distance={} state={}",
+//!             decoded.input_offset,
+//!             decoded.output_offset,
+//!             decoded.instruction,
+//!             decoded.len,
+//!             decoded.distance,
+//!             decoded.state,
+//!         );
+//!     }
+//!     assert!(matches > 0);
+//!
+//!     Ok(())
+//! }
+//! ```
+
+use crate::decompress::{Counter, InstructionDecoder, Kind, Sink};
+
+/// Kind of an instruction of a LZO1X stream.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Instruction {
+    /// Literal run encoded in the first byte of the stream
+    InitialLiterals,
+    /// Literal run of 4 or more bytes
+    Literals,
+    /// Copy of 2 bytes within 1kB or of 3 bytes within 2..3kB distance
+    M1,
+    /// Copy of 3..8 bytes within 2kB distance
+    M2,
+    /// Copy within 16kB distance
+    M3,
+    /// Copy within 16..48kB distance
+    M4,
+    /// Terminating M4
+    End,
+}
+
+/// An instruction decoded by [`Disassembler`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Decoded {
+    /// Kind of the instruction
+    pub instruction: Instruction,
+    /// Offset of the instruction in the stream
+    pub input_offset: usize,
+    /// Number of bytes decoded before the instruction
+    pub output_offset: usize,
+    /// Number of literals or of bytes copied from the output
+    pub len: usize,
+    /// Distance of a copy, 0 for literal runs
+    pub distance: usize,
+    /// Number of literals following the instruction. For literal runs, the
+    /// state they leave for the next instruction.
+    pub state: usize,
+}
+
+/// Iterator over the instructions of a raw LZO1X stream, created by [`disassemble`].
+///
+/// Iteration stops after the terminating M4 or the first error.
+#[derive(Debug, Clone)]
+pub struct Disassembler<'a> {
+    data: &'a [u8],
+    reader: &'a [u8],
+    decoder: InstructionDecoder,
+    output: Counter,
+    done: bool,
+}
+
+/// Returns an iterator over the instructions of the stream in `data`.
+#[must_use]
+pub const fn disassemble(data: &[u8]) -> Disassembler<'_> {
+    Disassembler {
+        data,
+        reader: data,
+        decoder: InstructionDecoder::new(false),
+        output: Counter(0),
+        done: false,
+    }
+}
+
+impl Disassembler<'_> {
+    /// Returns the offset of the next instruction in the stream.
+    #[must_use]
+    pub const fn input_offset(&self) -> usize {
+        self.data.len() - self.reader.len()
+    }
+
+    /// Returns the number of bytes decoded so far.
+    #[must_use]
+    pub const fn output_offset(&self) -> usize {
+        self.output.0
+    }
+
+    fn decode_next(&mut self) -> Result<Decoded, crate::Error> {
+        let input_offset = self.input_offset();
+        let output_offset = self.output_offset();
+        let inst = self.decoder.next(&mut self.reader)?;
+
+        let instruction = match inst.kind {
+            Kind::InitialLiterals => Instruction::InitialLiterals,
+            Kind::Literals => Instruction::Literals,
+            Kind::M1 => Instruction::M1,
+            Kind::M2 => Instruction::M2,
+            Kind::M3 => Instruction::M3,
+            Kind::M4 => Instruction::M4,
+            Kind::End => Instruction::End,
+            Kind::ZeroRun => return Err(crate::Error::Unknown),
+        };
+        let decoded = Decoded {
+            instruction,
+            input_offset,
+            output_offset,
+            len: inst.len,
+            distance: inst.distance,
+            state: inst.state,
+        };
+
+        match inst.kind {
+            Kind::InitialLiterals | Kind::Literals => {
+                self.output.literals(&mut self.reader, inst.len)?;
+            }
+            Kind::End => {
+                /* Ensure terminating M4 was encountered */
+                if inst.len != 3 {
+                    return Err(crate::Error::Unknown);
+                }
+                self.done = true;
+            }
+            _ => {
+                self.output.lookback(inst.distance, inst.len)?;
+                self.output.literals(&mut self.reader, inst.state)?;
+            }
+        }
+
+        Ok(decoded)
+    }
+}
+
+impl Iterator for Disassembler<'_> {
+    type Item = Result<Decoded, crate::Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+
+        let result = self.decode_next();
+        self.done |= result.is_err();
+        Some(result)
+    }
+}
@@ -0,0 +1,206 @@
+//! Length-prefixed framing of raw LZO1X data.
+//!
+//! Many formats store the uncompressed size of the data in front of raw
+//! LZO1X data, sometimes followed by the compressed size. A [`Framing`]
+//! describes the width and byte order of these fields and reads and writes
+//! frames, checking that the data decompresses to the stored size.
+//!
+//! ## Example
+//! ```rust
+//! use std::io::Cursor;
+//! use lzokay_native::framing::{Endian, Framing, Width};
+//!
+//! fn main() -> Result<(), lzokay_native::Error> {
+//!     let framing = Framing::new(Width::U32, Endian::Little).with_compressed_size();
+//!     let data = include_bytes!("../test-data/uncompressed/alice29.txt");
+//!
+//!     let mut frame = Vec::new();
+//!     framing.write(&mut frame, data)?;
+//!
+//!     let decompressed = framing.read(&mut Cursor::new(frame))?;
+//!     assert_eq!(decompressed, data);
+//!
+//!     Ok(())
+//! }
+//! ```
+
+use std::io::{Read, Write};
+
+use byteorder::{BigEndian, LittleEndian, ReadBytesExt, WriteBytesExt};
+
+use crate::compress::{compress_worst_size, Dict};
+use crate::decompress::{decompress_all_with_options, decompress_with_options, DecodeOptions};
+use crate::util::read_bytes;
+
+/// Width of the size fields of a frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Width {
+    /// 16 bit sizes
+    U16,
+    /// 32 bit sizes
+    U32,
+    /// 64 bit sizes
+    U64,
+}
+
+/// Byte order of the size fields of a frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Endian {
+    /// Little endian
+    Little,
+    /// Big endian
+    Big,
+}
+
+/// Layout of the size fields in front of the compressed data.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Framing {
+    /// Width of the size fields
+    pub width: Width,
+    /// Byte order of the size fields
+    pub endian: Endian,
+    /// Whether the uncompressed size is followed by the compressed size
+    pub compressed_size: bool,
+}
+
+impl Framing {
+    /// Creates a framing with only the uncompressed size in front of the data.
+    #[must_use]
+    pub const fn new(width: Width, endian: Endian) -> Self {
+        Self {
+            width,
+            endian,
+            compressed_size: false,
+        }
+    }
+
+    /// Returns the framing with the compressed size following the uncompressed size.
+    #[must_use]
+    pub const fn with_compressed_size(mut self) -> Self {
+        self.compressed_size = true;
+        self
+    }
+
+    /// Reads a frame and returns the decompressed data as a new [`Vec<u8>`].
+    ///
+    /// The reader is left positioned right after the frame.
+    ///
+    /// # Arguments
+    /// * `reader` - Reader positioned at the start of the frame
+    ///
+    /// # Errors
+    /// Will return [`Err`] if there was an I/O error, the compressed size is
+    /// larger than any compressed data of the stored size, the data does not
+    /// decompress or it does not decompress to the stored size.
+    pub fn read<I>(self, reader: &mut I) -> Result<Vec<u8>, crate::Error>
+    where
+        I: Read,
+    {
+        let expected = self.read_size(reader)?;
+        let options = DecodeOptions::new().with_exact_size(expected);
+
+        if self.compressed_size {
+            let compressed_size = self.read_size(reader)?;
+            /* Compressed data never exceeds the worst size of its output */
+            if compressed_size > compress_worst_size(expected.min(usize::MAX / 2)) {
+                return Err(crate::Error::InvalidHeader(
+                    "frame compressed size is too large",
+                ));
+            }
+            let data = read_bytes(reader, compressed_size)?;
+            decompress_all_with_options(&data, options)
+        } else {
+            decompress_with_options(reader, options)
+        }
+    }
+
+    /// Compresses a byte slice and writes it as a frame.
+    ///
+    /// # Arguments
+    /// * `writer` - Writer to write the frame to
+    /// * `data` - Data to compress
+    ///
+    /// # Errors
+    /// Will return [`Err`] if compression fails, a size does not fit the
+    /// size fields or there was an I/O error while writing to `writer`.
+    pub fn write<O>(self, writer: &mut O, data: &[u8]) -> Result<(), crate::Error>
+    where
+        O: Write,
+    {
+        self.write_with_dict(writer, data, &mut Dict::new())
+    }
+
+    /// Compresses a byte slice and writes it as a frame, reusing the work memory of `dict`.
+    ///
+    /// # Arguments
+    /// * `writer` - Writer to write the frame to
+    /// * `data` - Data to compress
+    /// * `dict` - Data structure to to store data in
+    ///
+    /// # Errors
+    /// See [`Framing::write`] for details on possible errors.
+    pub fn write_with_dict<O>(
+        self,
+        writer: &mut O,
+        data: &[u8],
+        dict: &mut Dict,
+    ) -> Result<(), crate::Error>
+    where
+        O: Write,
+    {
+        let mut compressed = crate::compress::compress_with_dict(data, dict)?;
+        if compressed.is_empty() {
+            /* Without a compressed size, the stream has to be terminated */
+            compressed.extend_from_slice(&[0x11, 0, 0]);
+        }
+
+        self.write_size(writer, data.len())?;
+        if self.compressed_size {
+            self.write_size(writer, compressed.len())?;
+        }
+        writer.write_all(&compressed)?;
+
+        Ok(())
+    }
+
+    fn read_size<I>(self, reader: &mut I) -> Result<usize, crate::Error>
+    where
+        I: Read,
+    {
+        let size = match (self.width, self.endian) {
+            (Width::U16, Endian::Little) => u64::from(reader.read_u16::<LittleEndian>()?),
+            (Width::U16, Endian::Big) => u64::from(reader.read_u16::<BigEndian>()?),
+            (Width::U32, Endian::Little) => u64::from(reader.read_u32::<LittleEndian>()?),
+            (Width::U32, Endian::Big) => u64::from(reader.read_u32::<BigEndian>()?),
+            (Width::U64, Endian::Little) => reader.read_u64::<LittleEndian>()?,
+            (Width::U64, Endian::Big) => reader.read_u64::<BigEndian>()?,
+        };
+
+        usize::try_from(size).map_err(|_| crate::Error::InvalidHeader("frame size is too large"))
+    }
+
+    fn write_size<O>(self, writer: &mut O, size: usize) -> Result<(), crate::Error>
+    where
+        O: Write,
+    {
+        let too_large = || crate::Error::InvalidHeader("size does not fit the frame size field");
+        match (self.width, self.endian) {
+            (Width::U16, Endian::Little) => {
+                writer.write_u16::<LittleEndian>(size.try_into().map_err(|_| too_large())?)?;
+            }
+            (Width::U16, Endian::Big) => {
+                writer.write_u16::<BigEndian>(size.try_into().map_err(|_| too_large())?)?;
+            }
+            (Width::U32, Endian::Little) => {
+                writer.write_u32::<LittleEndian>(size.try_into().map_err(|_| too_large())?)?;
+            }
+            (Width::U32, Endian::Big) => {
+                writer.write_u32::<BigEndian>(size.try_into().map_err(|_| too_large())?)?;
+            }
+            (Width::U64, Endian::Little) => writer.write_u64::<LittleEndian>(size as u64)?,
+            (Width::U64, Endian::Big) => writer.write_u64::<BigEndian>(size as u64)?,
+        }
+
+        Ok(())
+    }
+}
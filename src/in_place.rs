@@ -0,0 +1,143 @@
+//! In-place decompression of raw LZO1X streams.
+//!
+//! The compressed data is placed at the end of the buffer that receives the
+//! output, and the output grows from the start of the buffer towards it. This
+//! works as long as the output never overtakes the input still to be read,
+//! which is guaranteed if the buffer is at least [`margin`] bytes larger than
+//! the output. [`worst_case_margin`] bounds the margin for any stream.
+//!
+//! ## Example
+//! ```rust
+//! use lzokay_native::in_place;
+//!
+//! fn main() -> Result<(), lzokay_native::Error> {
+//!     let data = include_bytes!("../test-data/uncompressed/alice29.txt");
+//!     let compressed = lzokay_native::compress(data)?;
+//!
+//!     let mut buf = vec![0; data.len() + in_place::margin(&compressed)?];
+//!     let input_start = buf.len() - compressed.len();
+//!     buf[input_start..].copy_from_slice(&compressed);
+//!
+//!     let len = in_place::decompress_in_place(&mut buf, input_start)?;
+//!     assert_eq!(&buf[..len], data);
+//!
+//!     Ok(())
+//! }
+//! ```
+
+use std::io::Cursor;
+
+use crate::decompress::{decode, Counter, InstructionDecoder, Kind, Sink};
+
+/// Decompresses the stream in `buf[input_start..]` to the start of `buf` and
+/// returns the size of the output.
+///
+/// # Arguments
+/// * `buf` - Buffer holding the compressed data at its end
+/// * `input_start` - Offset of the compressed data in `buf`
+///
+/// # Errors
+/// Will return [`Err`] if `input_start` is past the end of `buf`, the stream
+/// does not decompress or the output would overwrite input that has not been
+/// read yet.
+pub fn decompress_in_place(buf: &mut [u8], input_start: usize) -> Result<usize, crate::Error> {
+    if input_start > buf.len() {
+        return Err(crate::Error::InvalidHeader(
+            "input start is past the end of the buffer",
+        ));
+    }
+    let mut decoder = InstructionDecoder::new(false);
+    let mut ip = input_start;
+    let mut op = 0;
+
+    loop {
+        let mut reader = &buf[ip..];
+        let inst = decoder.next(&mut reader)?;
+        ip = buf.len() - reader.len();
+
+        match inst.kind {
+            Kind::InitialLiterals | Kind::Literals => {
+                copy_literals(buf, &mut ip, &mut op, inst.len)?;
+                continue;
+            }
+            Kind::End => {
+                /* Ensure terminating M4 was encountered */
+                if inst.len != 3 {
+                    return Err(crate::Error::Unknown);
+                }
+                return Ok(op);
+            }
+            Kind::ZeroRun => return Err(crate::Error::Unknown),
+            Kind::M1 | Kind::M2 | Kind::M3 | Kind::M4 => {
+                if inst.distance == 0 || inst.distance > op {
+                    return Err(crate::Error::LookbehindOverrun);
+                }
+                if op + inst.len > ip {
+                    return Err(crate::Error::OutputOverrun);
+                }
+                let start = op - inst.distance;
+                if inst.distance >= inst.len {
+                    buf.copy_within(start..start + inst.len, op);
+                } else {
+                    for i in 0..inst.len {
+                        buf[op + i] = buf[start + i];
+                    }
+                }
+                op += inst.len;
+            }
+        }
+
+        /* Copy literal */
+        copy_literals(buf, &mut ip, &mut op, inst.state)?;
+    }
+}
+
+/// Returns the number of bytes the buffer for in-place decompression of
+/// `data` has to be larger than the output.
+///
+/// # Errors
+/// Will return [`Err`] if `data` does not decompress.
+pub fn margin(data: &[u8]) -> Result<usize, crate::Error> {
+    /* The output overtakes the input if it gets further ahead of it than the
+     * input start. Literals keep the distance, so checking it at instruction
+     * boundaries is enough.
+     */
+    let mut ahead = 0;
+    let mut output = Counter::default();
+    decode(
+        &mut Cursor::new(data),
+        &mut InstructionDecoder::new(false),
+        &mut output,
+        |reader, output| {
+            ahead = ahead.max(output.len() as i64 - reader.position() as i64);
+            false
+        },
+    )?;
+
+    Ok((ahead + data.len() as i64 - output.0 as i64).max(0) as usize)
+}
+
+/// Returns a margin for in-place decompression that is enough for any
+/// stream of `uncompressed_size` bytes produced by an LZO1X compressor.
+#[must_use]
+pub const fn worst_case_margin(uncompressed_size: usize) -> usize {
+    uncompressed_size / 16 + 64 + 3
+}
+
+/* Moves `len` literals from the input to the output. Moving data to the
+ * front keeps the output behind the input.
+ */
+fn copy_literals(
+    buf: &mut [u8],
+    ip: &mut usize,
+    op: &mut usize,
+    len: usize,
+) -> Result<(), crate::Error> {
+    if buf.len() - *ip < len {
+        return Err(std::io::Error::from(std::io::ErrorKind::UnexpectedEof).into());
+    }
+    buf.copy_within(*ip..*ip + len, *op);
+    *ip += len;
+    *op += len;
+    Ok(())
+}
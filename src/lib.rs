@@ -85,11 +85,69 @@ mod decompress;
 #[cfg(feature = "decompress")]
 pub use decompress::*;
 
+#[cfg(feature = "lzop")]
+pub mod lzop;
+
+#[cfg(feature = "lzo-rle")]
+pub mod lzo_rle;
+
+#[cfg(feature = "btrfs")]
+pub mod btrfs;
+
+#[cfg(feature = "paa")]
+pub mod paa;
+
+#[cfg(feature = "compressed-array")]
+pub mod compressed_array;
+
+#[cfg(feature = "retro")]
+pub mod retro;
+
+#[cfg(feature = "python-lzo")]
+pub mod python_lzo;
+
+#[cfg(feature = "openvpn")]
+pub mod openvpn;
+
+#[cfg(feature = "orc")]
+pub mod orc;
+
+#[cfg(feature = "squashfs")]
+pub mod squashfs;
+
+#[cfg(feature = "framing")]
+pub mod framing;
+
+#[cfg(feature = "detect")]
+pub mod detect;
+
+#[cfg(feature = "carve")]
+pub mod carve;
+
+#[cfg(feature = "seekable")]
+pub mod seekable;
+
+#[cfg(feature = "checkpoint")]
+pub mod checkpoint;
+
+#[cfg(feature = "in-place")]
+pub mod in_place;
+
+#[cfg(feature = "concat")]
+pub mod concat;
+
+#[cfg(feature = "disasm")]
+pub mod disasm;
+
+#[cfg(feature = "writer")]
+pub mod writer;
+
 mod util;
 
 pub use util::Error;
 
 #[cfg(test)]
+#[allow(clippy::unwrap_used)]
 mod tests {
     use std::fs;
     #[cfg(any(feature = "decompress", feature = "compress"))]
@@ -100,8 +158,20 @@ mod tests {
     #[cfg(any(feature = "decompress", feature = "compress"))]
     use sha1::Sha1;
 
+    /* Incompressible bytes from a linear congruential generator */
+    #[cfg(any(feature = "retro", feature = "openvpn"))]
+    fn noise(len: usize, seed: u32) -> Vec<u8> {
+        let mut state = seed;
+        (0..len)
+            .map(|_| {
+                state = state.wrapping_mul(1_103_515_245).wrapping_add(12345);
+                (state >> 24) as u8
+            })
+            .collect()
+    }
+
     #[test]
-    #[ignore]
+    #[ignore = "regenerates the compressed test data with minilzo"]
     fn generated_test_data() {
         let files = fs::read_dir("./test-data/uncompressed").unwrap();
 
@@ -205,6 +275,1206 @@ mod tests {
         }
     }
 
+    #[cfg(all(feature = "lzop", feature = "compress"))]
+    fn lzop_file(blocks: &[&[u8]]) -> Vec<u8> {
+        use byteorder::{BigEndian, WriteBytesExt};
+
+        use crate::lzop::{F_ADLER32_C, F_ADLER32_D, MAGIC};
+        use crate::util::adler32;
+
+        let mut header = Vec::new();
+        header.write_u16::<BigEndian>(0x1040).unwrap();
+        header.write_u16::<BigEndian>(0x2080).unwrap();
+        header.write_u16::<BigEndian>(0x0940).unwrap();
+        header.extend_from_slice(&[1, 5]);
+        header
+            .write_u32::<BigEndian>(F_ADLER32_D | F_ADLER32_C)
+            .unwrap();
+        header.write_u32::<BigEndian>(0o100_644).unwrap();
+        header.write_u64::<BigEndian>(0).unwrap();
+        header.push(0);
+
+        let mut file = MAGIC.to_vec();
+        file.extend_from_slice(&header);
+        file.write_u32::<BigEndian>(adler32(1, &header)).unwrap();
+
+        for block in blocks {
+            let compressed = crate::compress::compress(block).unwrap();
+            let stored = compressed.len() >= block.len();
+            let data = if stored { *block } else { &compressed[..] };
+
+            file.write_u32::<BigEndian>(block.len() as u32).unwrap();
+            file.write_u32::<BigEndian>(data.len() as u32).unwrap();
+            file.write_u32::<BigEndian>(adler32(1, block)).unwrap();
+            if !stored {
+                file.write_u32::<BigEndian>(adler32(1, data)).unwrap();
+            }
+            file.extend_from_slice(data);
+        }
+        file.write_u32::<BigEndian>(0).unwrap();
+
+        file
+    }
+
+    #[cfg(all(feature = "lzop", feature = "compress"))]
+    #[test]
+    fn lzop_index_test() {
+        use crate::lzop::{self, LzoIndex};
+
+        let data = fs::read("./test-data/uncompressed/alice29.txt").unwrap();
+        let mut blocks: Vec<&[u8]> = data.chunks(0x8000).collect();
+        blocks.push(b"stored");
+        let file = lzop_file(&blocks);
+
+        let index = LzoIndex::build(&mut Cursor::new(&file)).unwrap();
+        assert_eq!(index.len(), blocks.len());
+        assert_eq!(index.offsets()[0], 9 + 25 + 4);
+
+        let mut index_file = Vec::new();
+        index.write(&mut index_file).unwrap();
+        assert_eq!(index_file.len(), blocks.len() * 8);
+        assert_eq!(LzoIndex::read(&mut Cursor::new(index_file)).unwrap(), index);
+
+        let all = lzop::decompress(&mut Cursor::new(&file)).unwrap();
+        assert_eq!(all, blocks.concat());
+
+        let tail =
+            lzop::decompress_blocks(&mut Cursor::new(&file), &index, 2..index.len()).unwrap();
+        assert_eq!(tail, blocks[2..].concat());
+
+        let middle = lzop::decompress_blocks(&mut Cursor::new(&file), &index, 1..2).unwrap();
+        assert_eq!(middle, blocks[1]);
+
+        assert!(
+            lzop::decompress_blocks(&mut Cursor::new(&file), &index, 0..index.len() + 1).is_err()
+        );
+
+        let mut corrupt = file.clone();
+        let last = corrupt.len() - 6;
+        corrupt[last] ^= 1;
+        assert!(matches!(
+            lzop::decompress(&mut Cursor::new(corrupt)),
+            Err(crate::Error::ChecksumMismatch)
+        ));
+
+        /* Huge block sizes are rejected before anything is allocated */
+        let first = usize::try_from(index.offsets()[0]).unwrap();
+        let mut huge = file.clone();
+        huge[first..first + 8].copy_from_slice(&[0xff; 8]);
+        assert!(matches!(
+            lzop::decompress(&mut Cursor::new(huge)),
+            Err(crate::Error::InvalidHeader(_))
+        ));
+        let mut truncated = file[..first + 8].to_vec();
+        truncated[first..first + 8].copy_from_slice(&[4, 0, 0, 0, 4, 0, 0, 0]);
+        truncated.extend_from_slice(&[0; 8]);
+        assert!(matches!(
+            lzop::decompress(&mut Cursor::new(truncated)),
+            Err(crate::Error::IOError(_))
+        ));
+    }
+
+    #[cfg(feature = "lzo-rle")]
+    #[test]
+    fn lzo_rle_test() {
+        use crate::lzo_rle;
+
+        /* "A", zero run of 10 bytes with 1 trailing literal "B", terminator */
+        let stream = [17, 1, 18, b'A', 0x18 | 6, 0xfd, 0xff, 0, b'B', 0x11, 0, 0];
+        let mut expected = b"A".to_vec();
+        expected.extend_from_slice(&[0; 10]);
+        expected.push(b'B');
+        assert_eq!(lzo_rle::decompress_all(&stream, None).unwrap(), expected);
+
+        let mut page = vec![0u8; 4096];
+        page[100..140].copy_from_slice(&[0x5a; 40]);
+        page[3000..3013].copy_from_slice(b"lzo-rle page!");
+        let compressed = lzo_rle::compress(&page).unwrap();
+        assert_eq!(&compressed[..2], &[17, lzo_rle::VERSION]);
+        assert!(compressed.len() < 64);
+        assert_eq!(lzo_rle::decompress_all(&compressed, None).unwrap(), page);
+
+        let files = fs::read_dir("./test-data/uncompressed").unwrap();
+        for file in files {
+            let data = fs::read(file.unwrap().path()).unwrap();
+            let compressed = lzo_rle::compress(&data).unwrap();
+            assert_eq!(lzo_rle::decompress_all(&compressed, None).unwrap(), data);
+        }
+
+        /* Plain LZO1X streams are accepted as well */
+        let plain = fs::read("./test-data/compressed/fields.c.lzo").unwrap();
+        let data = fs::read("./test-data/uncompressed/fields.c").unwrap();
+        assert_eq!(lzo_rle::decompress_all(&plain, None).unwrap(), data);
+        assert!(lzo_rle::decompress_all(&[17, 0, 0], None)
+            .unwrap()
+            .is_empty());
+    }
+
+    #[cfg(feature = "btrfs")]
+    #[test]
+    fn btrfs_test() {
+        use crate::btrfs;
+
+        let data = fs::read("./test-data/uncompressed/alice29.txt").unwrap();
+        for sector_size in [256, btrfs::SECTOR_SIZE] {
+            let extent = btrfs::compress(&data, sector_size).unwrap();
+            assert_eq!(extent[..4], (extent.len() as u32).to_le_bytes());
+
+            let mut padded = extent.clone();
+            padded.resize(extent.len().next_multiple_of(sector_size), 0);
+            assert_eq!(btrfs::decompress(&padded, sector_size).unwrap(), data);
+        }
+
+        /* Second segment header moved to the next sector of 32 bytes */
+        let mut extent = vec![0, 0, 0, 0, 22, 0, 0, 0];
+        extent.extend_from_slice(&[17 + 18]);
+        extent.extend_from_slice(b"0123456789abcdefgh");
+        extent.extend_from_slice(&[0x11, 0, 0, 0, 0]);
+        extent.extend_from_slice(&[7, 0, 0, 0, 17 + 3, b'x', b'y', b'z', 0x11, 0, 0]);
+        extent[0] = extent.len() as u8;
+        assert_eq!(
+            btrfs::decompress(&extent, 32).unwrap(),
+            b"0123456789abcdefghxyz"
+        );
+        assert!(btrfs::decompress(&extent[..extent.len() - 1], 32).is_err());
+
+        for sector_size in [0, 2, 100] {
+            assert!(matches!(
+                btrfs::decompress(&extent, sector_size),
+                Err(crate::Error::InvalidHeader(_))
+            ));
+            assert!(matches!(
+                btrfs::compress(&data, sector_size),
+                Err(crate::Error::InvalidHeader(_))
+            ));
+        }
+    }
+
+    #[cfg(feature = "paa")]
+    #[test]
+    fn paa_read_test() {
+        use byteorder::{LittleEndian, WriteBytesExt};
+
+        use crate::paa::{Paa, PaaType};
+
+        let data = fs::read("./test-data/uncompressed/alice29.txt").unwrap();
+        let large = &data[..PaaType::Dxt5.mipmap_size(64, 64)];
+        let small = &data[..PaaType::Dxt5.mipmap_size(2, 2)];
+        let compressed = crate::compress::compress(large).unwrap();
+
+        let mut file = Vec::new();
+        file.write_u16::<LittleEndian>(0xff05).unwrap();
+        file.extend_from_slice(b"GGATCGVA");
+        file.write_u32::<LittleEndian>(4).unwrap();
+        file.extend_from_slice(&[0x80, 0x80, 0x80, 0xff]);
+        file.write_u16::<LittleEndian>(0).unwrap();
+        file.write_u16::<LittleEndian>(64 | 0x8000).unwrap();
+        file.write_u16::<LittleEndian>(64).unwrap();
+        file.write_u24::<LittleEndian>(compressed.len() as u32)
+            .unwrap();
+        file.extend_from_slice(&compressed);
+        file.write_u16::<LittleEndian>(2).unwrap();
+        file.write_u16::<LittleEndian>(2).unwrap();
+        file.write_u24::<LittleEndian>(small.len() as u32).unwrap();
+        file.extend_from_slice(small);
+        file.extend_from_slice(&[0; 6]);
+
+        let paa = Paa::read(&mut Cursor::new(&file)).unwrap();
+        assert_eq!(paa.paa_type, PaaType::Dxt5);
+        assert_eq!(paa.taggs.len(), 1);
+        assert_eq!(&paa.taggs[0].name, b"CGVA");
+        assert_eq!(paa.mipmaps.len(), 2);
+        assert_eq!(paa.mipmaps[0].width, 64);
+        assert!(paa.mipmaps[0].lzo_compressed);
+        assert_eq!(paa.mipmaps[0].data, large);
+        assert!(!paa.mipmaps[1].lzo_compressed);
+        assert_eq!(paa.mipmaps[1].data, small);
+
+        /* Mipmap with the wrong dimensions for its data */
+        file[22] = 32;
+        assert!(matches!(
+            Paa::read(&mut Cursor::new(&file)),
+            Err(crate::Error::SizeMismatch { .. })
+        ));
+    }
+
+    #[cfg(feature = "paa")]
+    #[test]
+    fn paa_write_test() {
+        use crate::paa::{Mipmap, Paa, PaaType, Tagg, LZO_MIN_SIZE, OFFS_TAGG};
+
+        let data = fs::read("./test-data/uncompressed/alice29.txt").unwrap();
+        let mipmaps = [256u16, 128, 64, 32, 16, 8, 4]
+            .iter()
+            .map(|&size| Mipmap {
+                width: size,
+                height: size,
+                lzo_compressed: false,
+                data: data[..PaaType::Dxt1.mipmap_size(size, size)].to_vec(),
+            })
+            .collect();
+        let paa = Paa {
+            paa_type: PaaType::Dxt1,
+            taggs: vec![
+                Tagg {
+                    name: *b"CXAM",
+                    data: vec![0xff; 4],
+                },
+                Tagg {
+                    name: OFFS_TAGG,
+                    data: vec![0x55; 64],
+                },
+            ],
+            palette: Vec::new(),
+            mipmaps,
+        };
+
+        let mut file = Vec::new();
+        paa.write(&mut file).unwrap();
+        assert!(file.ends_with(&[0; 6]));
+
+        let read = Paa::read(&mut Cursor::new(&file)).unwrap();
+        assert_eq!(read.taggs[0], paa.taggs[0]);
+        assert_eq!(read.mipmaps.len(), paa.mipmaps.len());
+        for (read, written) in read.mipmaps.iter().zip(&paa.mipmaps) {
+            assert_eq!(read.width, written.width);
+            assert_eq!(read.data, written.data);
+            assert_eq!(read.lzo_compressed, written.data.len() >= LZO_MIN_SIZE);
+        }
+
+        /* The offsets TAGG points at the mipmaps as written */
+        let offsets = &read.taggs[1].data;
+        assert_eq!(offsets.len(), 64);
+        for (i, mipmap) in paa.mipmaps.iter().enumerate() {
+            let offset = u32::from_le_bytes(offsets[i * 4..i * 4 + 4].try_into().unwrap());
+            let offset = offset as usize;
+            let height = u16::from_le_bytes([file[offset + 2], file[offset + 3]]);
+            assert_eq!(height, mipmap.height);
+        }
+        assert!(offsets[paa.mipmaps.len() * 4..].iter().all(|&b| b == 0));
+
+        /* An empty offsets TAGG is written at full size */
+        let short = Paa {
+            paa_type: PaaType::Dxt1,
+            taggs: vec![Tagg {
+                name: OFFS_TAGG,
+                data: Vec::new(),
+            }],
+            palette: Vec::new(),
+            mipmaps: vec![paa.mipmaps[2].clone()],
+        };
+        let mut file = Vec::new();
+        short.write(&mut file).unwrap();
+        let read = Paa::read(&mut Cursor::new(&file)).unwrap();
+        assert_eq!(read.taggs[0].data.len(), 64);
+        assert_eq!(read.taggs[0].data[..4], 80u32.to_le_bytes());
+        assert_eq!(read.mipmaps[0].data, short.mipmaps[0].data);
+
+        let mut invalid = paa.clone();
+        invalid.palette = vec![0; 4];
+        let mut file = Vec::new();
+        assert!(invalid.write(&mut file).is_err());
+        assert!(file.is_empty());
+
+        let mut invalid = paa.clone();
+        invalid.mipmaps[0].width = 0x8100;
+        assert!(invalid.write(&mut file).is_err());
+        assert!(file.is_empty());
+
+        let mut invalid = paa;
+        invalid.mipmaps = vec![invalid.mipmaps[6].clone(); 17];
+        assert!(invalid.write(&mut file).is_err());
+        assert!(file.is_empty());
+    }
+
+    #[cfg(feature = "compressed-array")]
+    #[test]
+    fn compressed_array_test() {
+        use crate::compressed_array::{self, THRESHOLD};
+
+        let data = fs::read("./test-data/uncompressed/alice29.txt").unwrap();
+        let large = &data[..THRESHOLD * 4];
+        let small = &data[..THRESHOLD - 4];
+
+        let mut dict = crate::Dict::new();
+        let mut file = Vec::new();
+        compressed_array::write_with_dict(&mut file, large, &mut dict).unwrap();
+        compressed_array::write_with_dict(&mut file, small, &mut dict).unwrap();
+        compressed_array::write_with_dict(&mut file, &[], &mut dict).unwrap();
+        assert!(file.len() < large.len());
+        file.extend_from_slice(b"tail");
+
+        let mut reader = Cursor::new(&file);
+        let read = compressed_array::read(&mut reader, THRESHOLD, 4).unwrap();
+        assert_eq!(read, large);
+        let read = compressed_array::read(&mut reader, (THRESHOLD - 4) / 4, 4).unwrap();
+        assert_eq!(read, small);
+        assert!(compressed_array::read(&mut reader, 0, 4)
+            .unwrap()
+            .is_empty());
+        assert_eq!(compressed_array::read(&mut reader, 4, 1).unwrap(), b"tail");
+
+        assert!(matches!(
+            compressed_array::read(&mut Cursor::new(&file), THRESHOLD / 2, 4),
+            Err(crate::Error::SizeMismatch { .. })
+        ));
+        assert!(matches!(
+            compressed_array::read(&mut Cursor::new(&file), usize::MAX, 12),
+            Err(crate::Error::InvalidHeader(_))
+        ));
+    }
+
+    #[cfg(feature = "retro")]
+    #[test]
+    fn retro_test() {
+        use crate::retro::{self, SEGMENT_SIZE};
+
+        let data = fs::read("./test-data/uncompressed/alice29.txt").unwrap();
+        let compressed = retro::compress(&data).unwrap();
+        assert!(compressed.len() < data.len());
+        assert_eq!(retro::decompress(&compressed, data.len()).unwrap(), data);
+
+        /* Incompressible segments are stored */
+        let noise = noise(SEGMENT_SIZE + 100, 1);
+        let compressed = retro::compress(&noise).unwrap();
+        assert_eq!(compressed[..2], (-(SEGMENT_SIZE as i16)).to_be_bytes());
+        assert_eq!(compressed.len(), noise.len() + 4);
+        assert_eq!(retro::decompress(&compressed, noise.len()).unwrap(), noise);
+
+        /* One stored and one compressed segment */
+        let mut segments = vec![0xff, 0xfd, b'a', b'b', b'c', 0, 7];
+        segments.extend_from_slice(&[17 + 3, b'x', b'y', b'z', 0x11, 0, 0]);
+        assert_eq!(retro::decompress(&segments, 6).unwrap(), b"abcxyz");
+        assert!(retro::decompress(&segments[..segments.len() - 1], 6).is_err());
+        assert!(matches!(
+            retro::decompress(&segments, 5),
+            Err(crate::Error::SizeMismatch { .. })
+        ));
+    }
+
+    #[cfg(feature = "python-lzo")]
+    #[test]
+    fn python_lzo_test() {
+        use crate::python_lzo::{self, LZO1X_1, LZO1X_999};
+
+        let data = fs::read("./test-data/uncompressed/alice29.txt").unwrap();
+        let mut compressed = python_lzo::compress(&data).unwrap();
+        assert_eq!(compressed[0], LZO1X_999);
+        assert_eq!(compressed[1..5], (data.len() as u32).to_be_bytes());
+        assert_eq!(python_lzo::decompress(&compressed).unwrap(), data);
+
+        compressed[0] = LZO1X_1;
+        assert_eq!(python_lzo::decompress(&compressed).unwrap(), data);
+
+        compressed[0] = 0xf2;
+        assert!(python_lzo::decompress(&compressed).is_err());
+        assert!(python_lzo::decompress(&[LZO1X_1, 0, 0]).is_err());
+
+        let compressed = python_lzo::compress(&[]).unwrap();
+        assert_eq!(compressed, [LZO1X_999, 0, 0, 0, 0, 0x11, 0, 0]);
+        assert!(python_lzo::decompress(&compressed).unwrap().is_empty());
+
+        /* lzo.compress(b"xyz") */
+        let blob = [LZO1X_1, 0, 0, 0, 3, 17 + 3, b'x', b'y', b'z', 0x11, 0, 0];
+        assert_eq!(python_lzo::decompress(&blob).unwrap(), b"xyz");
+        let mut blob = blob;
+        blob[4] = 4;
+        assert!(matches!(
+            python_lzo::decompress(&blob),
+            Err(crate::Error::SizeMismatch {
+                expected: 4,
+                actual: 3
+            })
+        ));
+    }
+
+    #[cfg(feature = "openvpn")]
+    #[test]
+    fn openvpn_test() {
+        use std::time::{Duration, Instant};
+
+        use crate::openvpn::{self, Compressor, LZO_COMPRESS_BYTE, NO_COMPRESS_BYTE};
+
+        let data = fs::read("./test-data/uncompressed/alice29.txt").unwrap();
+        let text = &data[..1400];
+        let short = &data[..openvpn::COMPRESS_THRESHOLD - 1];
+        let noise = noise(1400, 1);
+
+        let mut compressor = Compressor::new(false);
+        for (payload, marker) in [
+            (text, LZO_COMPRESS_BYTE),
+            (short, NO_COMPRESS_BYTE),
+            (&noise[..], NO_COMPRESS_BYTE),
+        ] {
+            let packet = compressor.compress(payload).unwrap();
+            assert_eq!(packet[0], marker);
+            assert_eq!(openvpn::decompress(&packet).unwrap(), payload);
+        }
+        assert!(compressor.compress(&[]).unwrap().is_empty());
+        assert!(openvpn::decompress(&[0x67, 1, 2]).is_err());
+
+        /* Incompressible traffic turns compression off for AC_OFF_SEC */
+        let start = Instant::now();
+        let mut compressor = Compressor::new(true);
+        compressor.compress_at(&noise, start).unwrap();
+        let stats = compressor.adaptive().unwrap();
+        assert!(!stats.compression_off);
+        assert_eq!(stats.n_total, noise.len());
+
+        let now = start + Duration::from_secs(openvpn::AC_SAMP_SEC);
+        let packet = compressor.compress_at(text, now).unwrap();
+        assert_eq!(packet[0], NO_COMPRESS_BYTE);
+        let stats = compressor.adaptive().unwrap();
+        assert!(stats.compression_off);
+        assert_eq!(stats.next, now + Duration::from_secs(openvpn::AC_OFF_SEC));
+        assert_eq!(stats.n_total, 0);
+
+        let packet = compressor.compress_at(text, stats.next).unwrap();
+        assert_eq!(packet[0], LZO_COMPRESS_BYTE);
+        assert!(!compressor.adaptive().unwrap().compression_off);
+    }
+
+    #[cfg(feature = "orc")]
+    #[test]
+    fn orc_test() {
+        use crate::orc;
+
+        let data = fs::read("./test-data/uncompressed/alice29.txt").unwrap();
+        for block_size in [1000, orc::DEFAULT_BLOCK_SIZE] {
+            let stream = orc::compress(&data, block_size).unwrap();
+            assert_eq!(orc::decompress(&stream, block_size).unwrap(), data);
+        }
+        assert!(orc::compress(&data, orc::MAX_CHUNK_LEN + 1).is_err());
+
+        /* One original and one compressed chunk */
+        let mut stream = vec![3 << 1 | 1, 0, 0, b'a', b'b', b'c', 7 << 1, 0, 0];
+        stream.extend_from_slice(&[17 + 3, b'x', b'y', b'z', 0x11, 0, 0]);
+        assert_eq!(orc::decompress(&stream, 3).unwrap(), b"abcxyz");
+        assert!(orc::decompress(&stream[..stream.len() - 1], 3).is_err());
+        assert!(matches!(
+            orc::decompress(&stream, 2),
+            Err(crate::Error::OutputOverrun)
+        ));
+
+        /* Incompressible chunks are stored as original */
+        let stream = orc::compress(b"abc", orc::DEFAULT_BLOCK_SIZE).unwrap();
+        assert_eq!(stream, [3 << 1 | 1, 0, 0, b'a', b'b', b'c']);
+    }
+
+    #[cfg(feature = "squashfs")]
+    #[test]
+    fn squashfs_test() {
+        use crate::squashfs::{self, Algorithm, CompOpts, METADATA_SIZE};
+
+        let opts = CompOpts::read(&[4, 0, 0, 0, 9, 0, 0, 0]).unwrap();
+        assert_eq!(opts.algorithm, Algorithm::Lzo1x999);
+        assert_eq!(opts.level, 9);
+        assert_eq!(CompOpts::read(&opts.to_bytes()).unwrap(), opts);
+        assert!(CompOpts::read(&[0, 0, 0, 0, 0, 0, 0, 0]).is_ok());
+        assert!(CompOpts::read(&[0, 0, 0, 0, 1, 0, 0, 0]).is_err());
+        assert!(CompOpts::read(&[4, 0, 0, 0, 10, 0, 0, 0]).is_err());
+        assert!(CompOpts::read(&[5, 0, 0, 0, 0, 0, 0, 0]).is_err());
+        assert!(CompOpts::read(&[4, 0, 0, 0]).is_err());
+
+        let data = fs::read("./test-data/uncompressed/alice29.txt").unwrap();
+        let mut dict = crate::Dict::new();
+        let block = squashfs::compress_block(&data[..0x20000], opts, &mut dict)
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            squashfs::decompress_block(&block, 0x20000).unwrap(),
+            &data[..0x20000]
+        );
+        assert!(squashfs::decompress_block(&block, 0x10000).is_err());
+        assert!(squashfs::compress_block(b"abc", opts, &mut dict)
+            .unwrap()
+            .is_none());
+
+        let lzo1x_1 = CompOpts {
+            algorithm: Algorithm::Lzo1x1,
+            level: 0,
+        };
+        assert!(!lzo1x_1.is_supported());
+        assert!(squashfs::compress_block(&data, lzo1x_1, &mut dict).is_err());
+
+        let mut image = Vec::new();
+        squashfs::write_metadata_block(&mut image, &data[..METADATA_SIZE], opts, &mut dict)
+            .unwrap();
+        squashfs::write_metadata_block(&mut image, b"abc", opts, &mut dict).unwrap();
+        assert!(squashfs::write_metadata_block(
+            &mut image,
+            &data[..=METADATA_SIZE],
+            opts,
+            &mut dict
+        )
+        .is_err());
+        assert_eq!(image[image.len() - 5..], [3, 0x80, b'a', b'b', b'c']);
+
+        let mut reader = Cursor::new(&image);
+        assert_eq!(
+            squashfs::read_metadata_block(&mut reader).unwrap(),
+            &data[..METADATA_SIZE]
+        );
+        assert_eq!(squashfs::read_metadata_block(&mut reader).unwrap(), b"abc");
+    }
+
+    #[cfg(feature = "framing")]
+    #[test]
+    fn framing_test() {
+        use crate::framing::{Endian, Framing, Width};
+
+        let data = fs::read("./test-data/uncompressed/alice29.txt").unwrap();
+        let data = &data[..0x8000];
+        for width in [Width::U16, Width::U32, Width::U64] {
+            for endian in [Endian::Little, Endian::Big] {
+                for framing in [
+                    Framing::new(width, endian),
+                    Framing::new(width, endian).with_compressed_size(),
+                ] {
+                    let mut frames = Vec::new();
+                    framing.write(&mut frames, data).unwrap();
+                    framing.write(&mut frames, b"").unwrap();
+
+                    let mut reader = Cursor::new(&frames);
+                    assert_eq!(framing.read(&mut reader).unwrap(), data);
+                    assert!(framing.read(&mut reader).unwrap().is_empty());
+                    assert_eq!(reader.position(), frames.len() as u64);
+                }
+            }
+        }
+
+        let framing = Framing::new(Width::U16, Endian::Big).with_compressed_size();
+        let frame = [0, 3, 0, 7, 17 + 3, b'x', b'y', b'z', 0x11, 0, 0];
+        assert_eq!(framing.read(&mut Cursor::new(frame)).unwrap(), b"xyz");
+        assert!(framing.read(&mut Cursor::new(&frame[..10])).is_err());
+        let mut frame = frame;
+        frame[1] = 4;
+        assert!(matches!(
+            framing.read(&mut Cursor::new(frame)),
+            Err(crate::Error::SizeMismatch {
+                expected: 4,
+                actual: 3
+            })
+        ));
+
+        assert!(framing.write(&mut Vec::new(), &vec![0; 0x10000]).is_err());
+
+        /* Declared sizes are not trusted for allocations */
+        let framing = Framing::new(Width::U64, Endian::Little).with_compressed_size();
+        let mut frame = vec![0xff; 16];
+        frame.extend_from_slice(&[0x11, 0, 0]);
+        assert!(matches!(
+            framing.read(&mut Cursor::new(&frame)),
+            Err(crate::Error::InvalidHeader(_))
+        ));
+        frame[..8].copy_from_slice(&3u64.to_le_bytes());
+        assert!(matches!(
+            framing.read(&mut Cursor::new(&frame)),
+            Err(crate::Error::InvalidHeader(_))
+        ));
+        let framing = Framing::new(Width::U64, Endian::Little);
+        assert!(matches!(
+            framing.read(&mut Cursor::new(&frame[8..])),
+            Err(crate::Error::SizeMismatch { actual: 0, .. })
+        ));
+    }
+
+    #[cfg(feature = "detect")]
+    #[test]
+    fn detect_test() {
+        use crate::detect::{self, Format};
+
+        let data = fs::read("./test-data/uncompressed/alice29.txt").unwrap();
+        let data = &data[..0x4000];
+
+        let lzop = lzop_file(&[data]);
+        let python = crate::python_lzo::compress(data).unwrap();
+        let raw = crate::compress::compress(data).unwrap();
+        for (blob, format) in [
+            (&lzop, Format::Lzop),
+            (&python, Format::PythonLzo),
+            (&raw, Format::Raw),
+        ] {
+            assert_eq!(detect::detect(blob), Some(format));
+            assert_eq!(detect::decompress_auto(blob).unwrap(), data);
+        }
+
+        /* A raw stream starting with 0xf0 is not a python-lzo header */
+        let mut raw = vec![0xf0];
+        raw.extend_from_slice(&data[..0xf0 - 17]);
+        raw.extend_from_slice(&[0x11, 0, 0]);
+        assert_eq!(detect::detect(&raw), Some(Format::Raw));
+
+        assert_eq!(detect::detect(data), None);
+        assert_eq!(detect::detect(&[]), None);
+        assert!(detect::decompress_auto(data).is_err());
+    }
+
+    #[cfg(all(feature = "carve", feature = "compress"))]
+    #[test]
+    fn carve_test() {
+        use crate::carve;
+
+        let alice = fs::read("./test-data/uncompressed/alice29.txt").unwrap();
+        let fields = fs::read("./test-data/compressed/fields.c.lzo").unwrap();
+        let first = crate::compress::compress(&alice[..0x8000]).unwrap();
+
+        let mut blob = alice[0x8000..0x8400].to_vec();
+        let first_offset = blob.len();
+        blob.extend_from_slice(&first);
+        blob.extend_from_slice(&[0xde, 0xad, 0xbe, 0xef]);
+        let second_offset = blob.len();
+        blob.extend_from_slice(&fields);
+        blob.extend_from_slice(&alice[0x8400..0x8800]);
+
+        let streams = carve::carve(&blob, 1024);
+        assert_eq!(streams.len(), 2);
+        assert_eq!(streams[0].offset, first_offset);
+        assert_eq!(streams[0].compressed_len, first.len());
+        assert_eq!(streams[0].decompressed_len, 0x8000);
+        assert_eq!(streams[0].slice(&blob), first);
+        assert_eq!(streams[1].offset, second_offset);
+        assert_eq!(streams[1].compressed_len, fields.len());
+
+        /* Truncated streams are rejected */
+        let streams = carve::carve(&blob[..second_offset + fields.len() - 1], 1024);
+        assert_eq!(streams.len(), 1);
+    }
+
+    #[cfg(feature = "seekable")]
+    #[test]
+    fn seekable_test() {
+        use std::io::{Read, Seek, SeekFrom};
+
+        use crate::seekable::{self, SeekableReader};
+
+        let data = fs::read("./test-data/uncompressed/alice29.txt").unwrap();
+        let container = seekable::compress(&data, 10_000).unwrap();
+
+        let mut reader = SeekableReader::new(Cursor::new(&container)).unwrap();
+        assert_eq!(reader.len(), data.len() as u64);
+        assert_eq!(reader.block_count(), data.len().div_ceil(10_000));
+
+        /* A read spanning two blocks */
+        let mut buf = vec![0; 5000];
+        reader.seek(SeekFrom::Start(57_500)).unwrap();
+        reader.read_exact(&mut buf).unwrap();
+        assert_eq!(buf, data[57_500..62_500]);
+
+        reader.seek(SeekFrom::End(-10)).unwrap();
+        let mut tail = Vec::new();
+        reader.read_to_end(&mut tail).unwrap();
+        assert_eq!(tail, data[data.len() - 10..]);
+        assert!(reader
+            .seek(SeekFrom::Current(-(data.len() as i64) - 1))
+            .is_err());
+
+        reader.rewind().unwrap();
+        let mut all = Vec::new();
+        reader.read_to_end(&mut all).unwrap();
+        assert_eq!(all, data);
+
+        let empty = seekable::compress(&[], 10_000).unwrap();
+        let mut reader = SeekableReader::new(Cursor::new(&empty)).unwrap();
+        assert!(reader.is_empty());
+        assert_eq!(reader.read(&mut buf).unwrap(), 0);
+
+        /* Corrupt data is detected by the block checksum */
+        let mut corrupt = container.clone();
+        corrupt[1] ^= 1;
+        let mut reader = SeekableReader::new(Cursor::new(&corrupt)).unwrap();
+        assert!(reader.read(&mut buf).is_err());
+        reader.seek(SeekFrom::Start(10_000)).unwrap();
+        assert!(reader.read(&mut buf).is_ok());
+
+        assert!(SeekableReader::new(Cursor::new(&container[1..])).is_err());
+        assert!(SeekableReader::new(Cursor::new(&container[..container.len() - 1])).is_err());
+    }
+
+    #[cfg(all(feature = "checkpoint", feature = "compress"))]
+    #[test]
+    fn checkpoint_test() {
+        use crate::checkpoint::{CheckpointIndex, WINDOW_SIZE};
+
+        let data = fs::read("./test-data/uncompressed/alice29.txt").unwrap();
+        let mut stream = b"prefix".to_vec();
+        stream.extend_from_slice(&crate::compress::compress(&data).unwrap());
+        let mut reader = Cursor::new(&stream);
+        reader.set_position(6);
+
+        let index = CheckpointIndex::build(&mut reader, 20_000).unwrap();
+        assert_eq!(index.len(), data.len() as u64);
+        let checkpoints = index.checkpoints();
+        assert!(checkpoints.len() >= data.len() / 20_000);
+        assert_eq!(checkpoints[0].input_offset, 6);
+        for cp in &checkpoints[1..] {
+            assert!(cp.window.len() == WINDOW_SIZE || cp.output_offset < WINDOW_SIZE as u64);
+            let end = cp.output_offset as usize;
+            assert_eq!(cp.window, data[end - cp.window.len()..end]);
+        }
+        let second = checkpoints[1].output_offset;
+        assert_eq!(index.nearest(second), &checkpoints[1]);
+        assert_eq!(index.nearest(second - 1), &checkpoints[0]);
+
+        for range in [
+            0..100,
+            50_000..50_001,
+            99_999..130_000,
+            0..data.len() as u64,
+        ] {
+            assert_eq!(
+                index.decompress_range(&mut reader, range.clone()).unwrap(),
+                data[range.start as usize..range.end as usize]
+            );
+        }
+        let end = data.len() as u64;
+        assert!(index.decompress_range(&mut reader, end - 1..end).is_ok());
+        assert!(matches!(
+            index.decompress_range(&mut reader, end..end + 1),
+            Err(crate::Error::RangeOutOfBounds { start, end: e }) if start == end && e == end + 1
+        ));
+        #[allow(clippy::reversed_empty_ranges)]
+        let reversed = 100..50;
+        assert!(matches!(
+            index.decompress_range(&mut reader, reversed),
+            Err(crate::Error::RangeOutOfBounds {
+                start: 100,
+                end: 50
+            })
+        ));
+    }
+
+    #[cfg(all(feature = "in-place", feature = "compress"))]
+    #[test]
+    fn in_place_test() {
+        use crate::in_place;
+
+        let files = fs::read_dir("./test-data/uncompressed").unwrap();
+        for file in files {
+            let data = fs::read(file.unwrap().path()).unwrap();
+            let compressed = crate::compress::compress(&data).unwrap();
+            let margin = in_place::margin(&compressed).unwrap();
+            assert!(margin <= in_place::worst_case_margin(data.len()));
+
+            let mut buf = vec![0; data.len() + margin];
+            let input_start = buf.len() - compressed.len();
+            buf[input_start..].copy_from_slice(&compressed);
+            let len = in_place::decompress_in_place(&mut buf, input_start).unwrap();
+            assert_eq!(buf[..len], data);
+
+            /* One byte less and the output overtakes the input */
+            if margin > 0 && data.len() + margin > compressed.len() {
+                let mut buf = vec![0; data.len() + margin - 1];
+                let input_start = buf.len() - compressed.len();
+                buf[input_start..].copy_from_slice(&compressed);
+                assert!(matches!(
+                    in_place::decompress_in_place(&mut buf, input_start),
+                    Err(crate::Error::OutputOverrun)
+                ));
+            }
+        }
+
+        /* Literals never overtake the input, the buffer only has to hold it */
+        let stream = [17 + 3, b'x', b'y', b'z', 0x11, 0, 0];
+        assert_eq!(in_place::margin(&stream).unwrap(), stream.len() - 3);
+        let mut buf = stream;
+        assert_eq!(in_place::decompress_in_place(&mut buf, 0).unwrap(), 3);
+        assert_eq!(buf[..3], *b"xyz");
+        assert!(matches!(
+            in_place::decompress_in_place(&mut buf, stream.len() + 1),
+            Err(crate::Error::InvalidHeader(_))
+        ));
+    }
+
+    #[cfg(all(feature = "concat", feature = "compress"))]
+    #[test]
+    fn concat_test() {
+        use crate::concat::concat_streams;
+
+        let data = fs::read("./test-data/uncompressed/alice29.txt").unwrap();
+        let mut dict = crate::Dict::new();
+        let mut streams = Vec::new();
+        let mut start = 0;
+        for len in [1, 2, 3, 5000, 3, 1, 238, 239, 30_000, 2, 4] {
+            let stream = crate::compress::compress_with_dict(&data[start..start + len], &mut dict);
+            streams.push(stream.unwrap());
+            start += len;
+        }
+        streams.push(Vec::new());
+        streams.push(vec![0x11, 0, 0]);
+
+        let slices: Vec<&[u8]> = streams.iter().map(Vec::as_slice).collect();
+        let joined = concat_streams(&slices).unwrap();
+        assert_eq!(
+            crate::decompress::decompress_all(&joined, None).unwrap(),
+            data[..start]
+        );
+        assert!(joined.len() < slices.iter().map(|s| s.len()).sum());
+        /* Instructions away from the junctions are copied verbatim */
+        let body = &streams[8][100..streams[8].len() - 100];
+        assert!(joined.windows(body.len()).any(|window| window == body));
+
+        /* An M1 of 2 bytes after 1 literal, following a literal run */
+        let second = [17 + 1, b'a', 0, 0, 0x11, 0, 0];
+        assert_eq!(
+            crate::decompress::decompress_all(&second, None).unwrap(),
+            b"aaa"
+        );
+        let first = [17 + 4, b'w', b'x', b'y', b'z', 0x11, 0, 0];
+        let joined = concat_streams(&[&first, &second]).unwrap();
+        assert_eq!(
+            crate::decompress::decompress_all(&joined, None).unwrap(),
+            b"wxyzaaa"
+        );
+
+        assert!(concat_streams(&[&first[..first.len() - 1], &second]).is_err());
+        assert_eq!(concat_streams(&[]).unwrap(), [0x11, 0, 0]);
+    }
+
+    #[cfg(all(feature = "disasm", feature = "compress"))]
+    #[test]
+    fn disasm_test() {
+        use crate::disasm::{disassemble, Instruction};
+
+        let data = fs::read("./test-data/uncompressed/alice29.txt").unwrap();
+        let compressed = crate::compress::compress(&data).unwrap();
+
+        let decoded: Vec<_> = disassemble(&compressed).map(Result::unwrap).collect();
+        assert_eq!(decoded[0].instruction, Instruction::InitialLiterals);
+        assert_eq!(decoded[0].input_offset, 0);
+        let last = decoded.last().unwrap();
+        assert_eq!(last.instruction, Instruction::End);
+        assert_eq!(last.input_offset, compressed.len() - 3);
+        assert_eq!(last.output_offset, data.len());
+
+        /* Offsets follow from the previous instruction */
+        for pair in decoded.windows(2) {
+            let (prev, next) = (pair[0], pair[1]);
+            let (copied, literals) = match prev.instruction {
+                Instruction::InitialLiterals | Instruction::Literals => (0, prev.len),
+                _ => (prev.len, prev.state),
+            };
+            assert!(next.input_offset > prev.input_offset + literals);
+            assert_eq!(next.output_offset, prev.output_offset + copied + literals);
+            assert!(next.distance <= next.output_offset);
+        }
+
+        /* Two literals followed by a M1 copying them, with one trailing literal */
+        let stream = [17 + 2, b'a', b'b', 0x05, 0, b'c', 0x11, 0, 0];
+        let decoded: Vec<_> = disassemble(&stream).map(Result::unwrap).collect();
+        assert_eq!(decoded.len(), 3);
+        assert_eq!(decoded[1].instruction, Instruction::M1);
+        assert_eq!((decoded[1].input_offset, decoded[1].output_offset), (3, 2));
+        assert_eq!(
+            (decoded[1].len, decoded[1].distance, decoded[1].state),
+            (2, 2, 1)
+        );
+        assert_eq!(decoded[2].output_offset, 5);
+
+        let mut truncated = disassemble(&stream[..4]);
+        assert!(truncated.nth(1).unwrap().is_err());
+        assert!(truncated.next().is_none());
+    }
+
+    #[cfg(all(feature = "writer", feature = "decompress", feature = "compress"))]
+    #[test]
+    fn writer_test() {
+        use crate::writer::LzoWriter;
+
+        let data = fs::read("./test-data/uncompressed/alice29.txt").unwrap();
+
+        /* Greedy parse matching the previous occurrence of each 4-byte sequence */
+        let mut writer = LzoWriter::default();
+        let mut last = std::collections::HashMap::new();
+        let (mut lit_start, mut pos) = (0, 0);
+        while pos + 4 <= data.len() {
+            let key = &data[pos..pos + 4];
+            match last.insert(key, pos) {
+                Some(prev) if pos - prev <= crate::writer::MAX_DISTANCE => {
+                    let len = data[pos..]
+                        .iter()
+                        .zip(&data[prev..])
+                        .take_while(|(a, b)| a == b)
+                        .count();
+                    writer.literals(&data[lit_start..pos]).unwrap();
+                    writer.lookback(len, pos - prev).unwrap();
+                    pos += len;
+                    lit_start = pos;
+                }
+                _ => pos += 1,
+            }
+        }
+        writer.literals(&data[lit_start..]).unwrap();
+        assert_eq!(writer.output_len(), data.len());
+        let stream = writer.finish();
+        assert!(stream.len() < data.len());
+        assert_eq!(
+            crate::decompress::decompress_all(&stream, None).unwrap(),
+            data
+        );
+
+        /* The compressor's parse results in the same bytes as compress */
+        let mut writer = LzoWriter::default();
+        for token in crate::compress::parse(&data) {
+            match token {
+                crate::compress::Token::Literals(lit) => writer.literals(lit).unwrap(),
+                crate::compress::Token::Match { len, offset } => {
+                    writer.lookback(len, offset).unwrap();
+                }
+            }
+        }
+        assert_eq!(writer.finish(), crate::compress::compress(&data).unwrap());
+
+        /* Every encoding of M1 to M4 */
+        let mut writer = LzoWriter::default();
+        writer.literals(&data[..0xc000]).unwrap();
+        for (len, distance) in [
+            (3, 0x900),
+            (2, 0x400),
+            (4, 0x800),
+            (40, 0x4000),
+            (3, 0xbfff),
+            (300, 0x4001),
+        ] {
+            writer.lookback(len, distance).unwrap();
+            writer.literals(b"x").unwrap();
+        }
+        let stream = writer.finish();
+        assert_eq!(
+            crate::decompress::decompress_all(&stream, None)
+                .unwrap()
+                .len(),
+            0xc000 + 6 + 3 + 2 + 4 + 40 + 3 + 300
+        );
+
+        let mut writer = LzoWriter::default();
+        assert!(matches!(
+            writer.lookback(3, 1),
+            Err(crate::Error::LookbehindOverrun)
+        ));
+        writer.literals(b"abcdef").unwrap();
+        assert!(writer.literals(b"g").is_err());
+        assert!(writer.lookback(2, 1).is_err());
+        assert!(writer.lookback(1, 1).is_err());
+        writer.lookback(3, 6).unwrap();
+        writer.literals(b"gh").unwrap();
+        writer.lookback(2, 2).unwrap();
+        assert_eq!(
+            crate::decompress::decompress_all(&writer.finish(), None).unwrap(),
+            b"abcdefabcghgh"
+        );
+    }
+
+    #[cfg(feature = "compress")]
+    #[test]
+    fn parse_test() {
+        use crate::compress::{parse, parse_with_dict, Token};
+
+        let data = fs::read("./test-data/uncompressed/alice29.txt").unwrap();
+        let mut dict = crate::Dict::new();
+
+        let mut output = Vec::new();
+        let mut matches = 0;
+        for token in parse_with_dict(&data, &mut dict) {
+            match token {
+                Token::Literals(lit) => {
+                    assert!(!lit.is_empty());
+                    output.extend_from_slice(lit);
+                }
+                Token::Match { len, offset } => {
+                    assert!(len >= 2 && offset <= output.len());
+                    let start = output.len() - offset;
+                    for i in start..start + len {
+                        output.push(output[i]);
+                    }
+                    matches += 1;
+                }
+            }
+        }
+        assert_eq!(output, data);
+        assert!(matches > 1000);
+
+        assert!(parse(&data).eq(parse_with_dict(&data, &mut dict)));
+        assert_eq!(parse(b"").next(), None);
+        assert_eq!(parse(b"a").collect::<Vec<_>>(), [Token::Literals(b"a")]);
+    }
+
+    #[cfg(feature = "decompress")]
+    #[test]
+    fn validate_test() {
+        use crate::decompress::{decompressed_size, validate};
+
+        let data = fs::read("./test-data/compressed/alice29.txt.lzo").unwrap();
+        let expected = fs::read("./test-data/uncompressed/alice29.txt").unwrap();
+
+        let mut trailing = data.clone();
+        trailing.extend_from_slice(b"junk");
+        let validation = validate(&mut trailing.as_slice());
+        assert!(validation.is_valid());
+        assert_eq!(validation.output_len, expected.len());
+        assert_eq!(validation.input_len, data.len() as u64);
+        assert_eq!(decompressed_size(&data).unwrap(), expected.len());
+
+        let validation = validate(&mut &data[..data.len() - 1]);
+        assert!(matches!(validation.error, Some(crate::Error::IOError(_))));
+        assert_eq!(validation.input_len, data.len() as u64 - 1);
+        assert!(validation.output_len > 0);
+
+        /* A match reaching before the start of the output */
+        let validation = validate(&mut [17 + 1, b'a', 0x40, 0x01, 0x11, 0, 0].as_slice());
+        assert!(matches!(
+            validation.error,
+            Some(crate::Error::LookbehindOverrun)
+        ));
+        assert_eq!((validation.output_len, validation.input_len), (1, 4));
+        assert!(decompressed_size(&[0x12, b'a', 0x40, 0x01, 0x11, 0, 0]).is_err());
+    }
+
+    #[cfg(feature = "decompress")]
+    #[test]
+    fn decode_options_test() {
+        use crate::decompress::{decompress_all_with_options, DecodeOptions, EndOfStream};
+
+        let data = fs::read("./test-data/compressed/alice29.txt.lzo").unwrap();
+        let expected = fs::read("./test-data/uncompressed/alice29.txt").unwrap();
+        let strict = DecodeOptions::new();
+        let lenient = DecodeOptions::new().with_end(EndOfStream::TerminatorOrEof);
+        assert_eq!(strict, DecodeOptions::default());
+
+        /* Terminator required */
+        assert_eq!(
+            decompress_all_with_options(&data, strict).unwrap(),
+            expected
+        );
+        let unterminated = &data[..data.len() - 3];
+        assert!(matches!(
+            decompress_all_with_options(unterminated, strict),
+            Err(crate::Error::IOError(_))
+        ));
+
+        /* End of input at an instruction boundary */
+        assert_eq!(
+            decompress_all_with_options(unterminated, lenient).unwrap(),
+            expected
+        );
+        assert_eq!(
+            decompress_all_with_options(&data, lenient).unwrap(),
+            expected
+        );
+        assert!(decompress_all_with_options(&data[..data.len() - 2], lenient).is_err());
+        assert!(decompress_all_with_options(&[], lenient)
+            .unwrap()
+            .is_empty());
+        assert!(decompress_all_with_options(&[], strict).is_err());
+
+        /* Stop once enough bytes are produced */
+        let options = strict.with_stop_at(10_000);
+        assert_eq!(
+            decompress_all_with_options(&data, options).unwrap(),
+            expected[..10_000]
+        );
+        let options = lenient.with_stop_at(expected.len() + 1);
+        assert_eq!(
+            decompress_all_with_options(unterminated, options).unwrap(),
+            expected
+        );
+
+        /* Trailing data */
+        let mut trailing = data.clone();
+        trailing.push(0);
+        assert_eq!(
+            decompress_all_with_options(&trailing, strict).unwrap(),
+            expected
+        );
+        assert!(matches!(
+            decompress_all_with_options(&trailing, strict.with_reject_trailing()),
+            Err(crate::Error::TrailingData)
+        ));
+        assert!(matches!(
+            decompress_all_with_options(&trailing, lenient.with_reject_trailing()),
+            Err(crate::Error::TrailingData)
+        ));
+        assert_eq!(
+            decompress_all_with_options(&data, strict.with_reject_trailing()).unwrap(),
+            expected
+        );
+        let options = strict.with_stop_at(100).with_reject_trailing();
+        assert_eq!(
+            decompress_all_with_options(&trailing, options).unwrap(),
+            expected[..100]
+        );
+    }
+
+    #[cfg(feature = "decompress")]
+    #[test]
+    fn exact_size_test() {
+        use crate::decompress::{
+            decompress_all_with_options, decompress_with_options, DecodeOptions,
+        };
+
+        let data = fs::read("./test-data/compressed/alice29.txt.lzo").unwrap();
+        let expected = fs::read("./test-data/uncompressed/alice29.txt").unwrap();
+        let exact = |size| DecodeOptions::new().with_exact_size(size);
+
+        assert_eq!(
+            decompress_all_with_options(&data, exact(expected.len())).unwrap(),
+            expected
+        );
+        assert!(matches!(
+            decompress_all_with_options(&data, exact(expected.len() + 1)),
+            Err(crate::Error::SizeMismatch { expected: e, actual: a }) if e == expected.len() + 1 && a == expected.len()
+        ));
+        assert!(matches!(
+            decompress_all_with_options(&data, exact(expected.len() - 1)),
+            Err(crate::Error::SizeMismatch { actual, .. }) if actual > expected.len() - 1
+        ));
+
+        /* Decoding stops at the instruction growing the output past the size */
+        let mut reader = data.as_slice();
+        assert!(decompress_with_options(&mut reader, exact(1000)).is_err());
+        assert!(data.len() - reader.len() < data.len() / 10);
+
+        assert!(decompress_all_with_options(&[0x11, 0, 0], exact(0))
+            .unwrap()
+            .is_empty());
+        assert!(matches!(
+            decompress_all_with_options(&[0x11, 0, 0], exact(usize::MAX)),
+            Err(crate::Error::SizeMismatch { actual: 0, .. })
+        ));
+    }
+
+    #[cfg(feature = "decompress")]
+    #[test]
+    fn partial_test() {
+        use crate::decompress::decompress_partial;
+
+        let data = fs::read("./test-data/compressed/alice29.txt.lzo").unwrap();
+        let expected = fs::read("./test-data/uncompressed/alice29.txt").unwrap();
+
+        let partial = decompress_partial(&mut data.as_slice());
+        assert!(partial.error.is_none());
+        assert_eq!(partial.output, expected);
+        assert_eq!(partial.input_offset, data.len() as u64);
+
+        /* Truncated stream */
+        let partial = decompress_partial(&mut &data[..data.len() / 2]);
+        assert!(matches!(partial.error, Some(crate::Error::IOError(_))));
+        assert!(partial.input_offset <= data.len() as u64 / 2);
+        assert!(partial.output.len() > expected.len() / 3);
+        assert_eq!(partial.output, expected[..partial.output.len()]);
+
+        /* Corrupt match in the middle of the stream */
+        let mut corrupt = vec![17 + 4, b'a', b'b', b'c', b'd', 0x40, 0x00];
+        corrupt.extend_from_slice(&[0x40, 0xff, 0x11, 0, 0]);
+        let partial = decompress_partial(&mut corrupt.as_slice());
+        assert!(matches!(
+            partial.error,
+            Some(crate::Error::LookbehindOverrun)
+        ));
+        assert_eq!(partial.output, b"abcdddd");
+        assert_eq!(partial.input_offset, 7);
+    }
+
     #[cfg(feature = "decompress")]
     #[test]
     fn decompress_test_small() {
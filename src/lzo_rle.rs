@@ -0,0 +1,216 @@
+//! Support for the Linux kernel's `lzo-rle` variant of LZO1X, as used by
+//! zram and zswap.
+//!
+//! An `lzo-rle` stream starts with the byte `17` followed by a bitstream
+//! version byte. Version `1` adds a run-length instruction for runs of zero
+//! bytes, reusing the M4 encoding with `H = 1` and a distance of `0x7fff`.
+//! Streams without the version header are plain LZO1X and are decoded as such.
+//!
+//! ## Example
+//! ```rust
+//! fn main() -> Result<(), lzokay_native::Error> {
+//!     let page = [0u8; 4096];
+//!
+//!     let compressed = lzokay_native::lzo_rle::compress(&page)?;
+//!     let decompressed = lzokay_native::lzo_rle::decompress_all(&compressed, Some(4096))?;
+//!     assert_eq!(decompressed, page);
+//!
+//!     Ok(())
+//! }
+//! ```
+
+use std::io::{Read, Seek, SeekFrom};
+use std::ops::Range;
+
+use byteorder::ReadBytesExt;
+
+use crate::compress::{parse_with_dict, Dict, Token};
+use crate::decompress::{decompress_into, MAX_ZERO_RUN_LENGTH, MIN_ZERO_RUN_LENGTH};
+use crate::util::peek_u8;
+use crate::writer::LzoWriter;
+
+/// Bitstream version written by [`compress`], the one used by the kernel.
+pub const VERSION: u8 = 1;
+
+/// Compresses a byte slice into an `lzo-rle` stream and returns the result as
+/// a new [`Vec<u8>`].
+///
+/// # Arguments
+/// * `data` - Data to compress
+///
+/// # Errors
+/// Will return [`Err`] if compression fails.
+pub fn compress(data: &[u8]) -> Result<Vec<u8>, crate::Error> {
+    compress_with_dict(data, &mut Dict::new())
+}
+
+/// Compresses a byte slice into an `lzo-rle` stream and returns the result as
+/// a new [`Vec<u8>`], reusing the work memory of `dict`.
+///
+/// # Arguments
+/// * `data` - Data to compress
+/// * `dict` - Data structure to to store data in
+///
+/// # Errors
+/// See [`compress`] for details on possible errors.
+pub fn compress_with_dict(data: &[u8], dict: &mut Dict) -> Result<Vec<u8>, crate::Error> {
+    let mut encoder = RleEncoder {
+        data,
+        writer: LzoWriter::new(vec![17, VERSION]),
+        lit_start: 0,
+        pos: 0,
+    };
+
+    for token in parse_with_dict(data, dict) {
+        match token {
+            Token::Literals(lit) => encoder.pos += lit.len(),
+            Token::Match { len, offset } => encoder.lookback(len, offset),
+        }
+    }
+    encoder.flush_literals();
+
+    Ok(encoder.writer.finish())
+}
+
+/// Decompresses an `lzo-rle` reader and returns the result as a new [`Vec<u8>`].
+///
+/// # Arguments
+/// * `reader` - Reader with compressed data
+/// * `expected_size` - Expected size of output. This is used to allocate the right amount of memory from the start.
+///
+/// # Errors
+/// Will return [`Err`] if there was any kind of I/O error while reading from `reader` or if an unexpected byte is encountered.
+pub fn decompress<I>(reader: &mut I, expected_size: Option<usize>) -> Result<Vec<u8>, crate::Error>
+where
+    I: Read + Seek,
+{
+    let mut result = Vec::<u8>::with_capacity(expected_size.unwrap_or_default());
+
+    /* A plain stream never starts with 17, except for the empty stream 17 0 0 */
+    let mut rle = false;
+    if peek_u8(reader)? == 17 {
+        let pos = reader.stream_position()?;
+        reader.read_u8()?;
+        rle = reader.read_u8()? != 0;
+        if !rle {
+            reader.seek(SeekFrom::Start(pos))?;
+        }
+    }
+
+    decompress_into(reader, &mut result, rle)?;
+
+    Ok(result)
+}
+
+/// Decompresses an `lzo-rle` byte slice and returns the result as a new [`Vec<u8>`].
+///
+/// # Arguments
+/// * `data` - Data to decompress
+/// * `expected_size` - Expected size of output. This is used to allocate the right amount of memory from the start.
+///
+/// # Errors
+/// See [`decompress`] for details on possible errors.
+pub fn decompress_all(data: &[u8], expected_size: Option<usize>) -> Result<Vec<u8>, crate::Error> {
+    let mut data_reader = std::io::Cursor::new(data);
+
+    decompress(&mut data_reader, expected_size)
+}
+
+/* Turns the compressor's parse into lzo-rle instructions */
+struct RleEncoder<'a> {
+    data: &'a [u8],
+    writer: LzoWriter,
+    lit_start: usize,
+    pos: usize,
+}
+
+impl RleEncoder<'_> {
+    fn lookback(&mut self, len: usize, distance: usize) {
+        if len >= MIN_ZERO_RUN_LENGTH && self.data[self.pos..self.pos + len].iter().all(|&b| b == 0)
+        {
+            self.flush_literals();
+            self.zero_runs(len);
+        } else if is_ambiguous(len, distance) {
+            /* These M4 encodings read as a zero run, split or drop the match */
+            if len >= 6 {
+                self.lookback(len / 2, distance);
+                self.lookback(len - len / 2, distance);
+            } else {
+                self.pos += len;
+            }
+            return;
+        } else if len == 2
+            && !(1..=3).contains(&self.split_literals().last().map_or(0, |(lit, _)| lit.len()))
+        {
+            /* Splitting out zero runs changed the state this match relied on */
+            self.pos += len;
+            return;
+        } else {
+            self.flush_literals();
+            self.writer.encode_lookback(len, distance);
+        }
+        self.pos += len;
+        self.lit_start = self.pos;
+    }
+
+    /* Emits pending literals, splitting out zero runs */
+    fn flush_literals(&mut self) {
+        for (lit, zeros) in self.split_literals() {
+            self.writer.encode_literals(&self.data[lit]);
+            if zeros > 0 {
+                self.zero_runs(zeros);
+            }
+        }
+        self.lit_start = self.pos;
+    }
+
+    /* Splits the pending literals into literal runs, each followed by a run of
+     * zeros. The last zero run is empty. The first byte of the stream is always
+     * a literal as the first instruction can't be a zero run.
+     */
+    fn split_literals(&self) -> Vec<(Range<usize>, usize)> {
+        let mut runs = Vec::new();
+        let mut start = self.lit_start;
+        let mut i = start.max(1);
+        while i < self.pos {
+            let zeros = self.data[i..self.pos]
+                .iter()
+                .take_while(|&&b| b == 0)
+                .count();
+            if zeros >= MIN_ZERO_RUN_LENGTH {
+                runs.push((start..i, zeros));
+                start = i + zeros;
+            }
+            i += zeros.max(1);
+        }
+        runs.push((start..self.pos, 0));
+        runs
+    }
+
+    fn zero_runs(&mut self, mut len: usize) {
+        while len > 0 {
+            let mut run = len.min(MAX_ZERO_RUN_LENGTH);
+            if len - run != 0 && len - run < MIN_ZERO_RUN_LENGTH {
+                run = len - MIN_ZERO_RUN_LENGTH;
+            }
+            self.writer.zero_run(run);
+            len -= run;
+        }
+    }
+}
+
+/* Whether the M4 encoding of a match could be mistaken for a zero run:
+ * H = 1, the low 6 bits of the distance set and either the whole distance
+ * set (short match) or a first length byte of 252..255 (long match).
+ */
+const fn is_ambiguous(len: usize, distance: usize) -> bool {
+    if distance <= 0x4000 {
+        return false;
+    }
+    let off = distance - 0x4000;
+    if len <= 9 {
+        off & 0x7fff == 0x7fff
+    } else {
+        off & 0x403f == 0x403f && len - 9 >= 252 && len - 9 <= 255
+    }
+}
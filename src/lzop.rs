@@ -0,0 +1,448 @@
+//! Reading of [lzop](https://www.lzop.org/) files and their
+//! [hadoop-lzo](https://github.com/twitter/hadoop-lzo) split index.
+//!
+//! hadoop-lzo makes lzop files splittable by storing the file offset of every
+//! compressed block in a sidecar `.lzo.index` file, encoded as a sequence of
+//! big-endian `u64`. [`LzoIndex`] can generate such an index from an lzop
+//! stream and [`decompress_blocks`] uses it to start decompression at an
+//! arbitrary block.
+//!
+//! ## Example
+//! ```rust,no_run
+//! use std::fs::File;
+//! use lzokay_native::lzop::{self, LzoIndex};
+//!
+//! fn main() -> Result<(), lzokay_native::Error> {
+//!     let mut file = File::open("archive.lzo")?;
+//!     let index = LzoIndex::build(&mut file)?;
+//!     index.write(&mut File::create("archive.lzo.index")?)?;
+//!
+//!     // Decompress the second half of the archive only
+//!     let half = index.len() / 2;
+//!     let tail = lzop::decompress_blocks(&mut file, &index, half..index.len())?;
+//!
+//!     Ok(())
+//! }
+//! ```
+
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::ops::Range;
+
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+
+use crate::util::{adler32, crc32, read_bytes};
+
+/// Magic bytes every lzop file starts with.
+pub const MAGIC: [u8; 9] = [0x89, b'L', b'Z', b'O', 0x00, 0x0d, 0x0a, 0x1a, 0x0a];
+
+/// Header flag: blocks carry an Adler-32 checksum of the uncompressed data.
+pub const F_ADLER32_D: u32 = 0x0000_0001;
+/// Header flag: blocks carry an Adler-32 checksum of the compressed data.
+pub const F_ADLER32_C: u32 = 0x0000_0002;
+/// Header flag: the header contains an extra field.
+pub const F_H_EXTRA_FIELD: u32 = 0x0000_0040;
+/// Header flag: blocks carry a CRC-32 checksum of the uncompressed data.
+pub const F_CRC32_D: u32 = 0x0000_0100;
+/// Header flag: blocks carry a CRC-32 checksum of the compressed data.
+pub const F_CRC32_C: u32 = 0x0000_0200;
+/// Header flag: the header contains a filter id.
+pub const F_H_FILTER: u32 = 0x0000_0800;
+/// Header flag: the header checksum is a CRC-32 instead of an Adler-32.
+pub const F_H_CRC32: u32 = 0x0000_1000;
+
+/// Largest uncompressed block size accepted, the limit used by lzop itself.
+pub const MAX_BLOCK_SIZE: u32 = 64 * 1024 * 1024;
+
+/// Header of an lzop file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Header {
+    /// Version of lzop that wrote the file
+    pub version: u16,
+    /// Version of the LZO library used by lzop
+    pub lib_version: u16,
+    /// Minimum lzop version required to extract the file
+    pub version_needed: u16,
+    /// Compression method, `1`..=`3` are the LZO1X variants
+    pub method: u8,
+    /// Compression level
+    pub level: u8,
+    /// Header flags, see the `F_*` constants
+    pub flags: u32,
+    /// Filter id, `0` if no filter was used
+    pub filter: u32,
+    /// Unix mode of the original file
+    pub mode: u32,
+    /// Modification time of the original file
+    pub mtime: u64,
+    /// Name of the original file
+    pub name: String,
+}
+
+/// Header of a single block of an lzop file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BlockHeader {
+    /// Size of the block once decompressed
+    pub uncompressed_size: u32,
+    /// Size of the block as stored in the file
+    pub compressed_size: u32,
+    /// Adler-32 checksum of the uncompressed data
+    pub uncompressed_adler32: Option<u32>,
+    /// CRC-32 checksum of the uncompressed data
+    pub uncompressed_crc32: Option<u32>,
+    /// Adler-32 checksum of the compressed data
+    pub compressed_adler32: Option<u32>,
+    /// CRC-32 checksum of the compressed data
+    pub compressed_crc32: Option<u32>,
+}
+
+/// A hadoop-lzo split index, holding the file offset of every block of an lzop file.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LzoIndex {
+    offsets: Vec<u64>,
+}
+
+/* Records every byte read, used to compute the header checksum */
+struct RecordingReader<'a, R> {
+    inner: &'a mut R,
+    bytes: Vec<u8>,
+}
+
+impl<R: Read> Read for RecordingReader<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.bytes.extend_from_slice(&buf[..n]);
+        Ok(n)
+    }
+}
+
+impl Header {
+    /// Reads and verifies the header of an lzop file, including its magic.
+    ///
+    /// # Errors
+    /// Will return [`Err`] if there was an I/O error, the magic is missing or
+    /// the header checksum does not match.
+    pub fn read<I>(reader: &mut I) -> Result<Self, crate::Error>
+    where
+        I: Read,
+    {
+        let mut magic = [0u8; 9];
+        reader.read_exact(&mut magic)?;
+        if magic != MAGIC {
+            return Err(crate::Error::InvalidHeader("missing lzop magic"));
+        }
+
+        let mut rec = RecordingReader {
+            inner: reader,
+            bytes: Vec::new(),
+        };
+
+        let version = rec.read_u16::<BigEndian>()?;
+        let lib_version = rec.read_u16::<BigEndian>()?;
+        let version_needed = if version >= 0x0940 {
+            rec.read_u16::<BigEndian>()?
+        } else {
+            0
+        };
+        let method = rec.read_u8()?;
+        let level = if version >= 0x0940 { rec.read_u8()? } else { 0 };
+        let flags = rec.read_u32::<BigEndian>()?;
+        let filter = if flags & F_H_FILTER != 0 {
+            rec.read_u32::<BigEndian>()?
+        } else {
+            0
+        };
+        let mode = rec.read_u32::<BigEndian>()?;
+        let mut mtime = u64::from(rec.read_u32::<BigEndian>()?);
+        if version >= 0x0940 {
+            mtime |= u64::from(rec.read_u32::<BigEndian>()?) << 32;
+        }
+        let name_len = rec.read_u8()? as usize;
+        let name = String::from_utf8_lossy(&read_bytes(&mut rec, name_len)?).into_owned();
+
+        let header = Self {
+            version,
+            lib_version,
+            version_needed,
+            method,
+            level,
+            flags,
+            filter,
+            mode,
+            mtime,
+            name,
+        };
+
+        let checksum = header.header_checksum(&rec.bytes);
+        if reader.read_u32::<BigEndian>()? != checksum {
+            return Err(crate::Error::ChecksumMismatch);
+        }
+
+        if flags & F_H_EXTRA_FIELD != 0 {
+            let mut rec = RecordingReader {
+                inner: reader,
+                bytes: Vec::new(),
+            };
+            let extra_len = rec.read_u32::<BigEndian>()? as usize;
+            read_bytes(&mut rec, extra_len)?;
+
+            let checksum = header.header_checksum(&rec.bytes);
+            if reader.read_u32::<BigEndian>()? != checksum {
+                return Err(crate::Error::ChecksumMismatch);
+            }
+        }
+
+        Ok(header)
+    }
+
+    fn header_checksum(&self, data: &[u8]) -> u32 {
+        if self.flags & F_H_CRC32 == 0 {
+            adler32(1, data)
+        } else {
+            crc32(0, data)
+        }
+    }
+
+    fn check_supported(&self) -> Result<(), crate::Error> {
+        if !(1..=3).contains(&self.method) {
+            return Err(crate::Error::InvalidHeader("unsupported lzop method"));
+        }
+        if self.filter != 0 {
+            return Err(crate::Error::InvalidHeader(
+                "lzop filters are not supported",
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl BlockHeader {
+    /// Reads the header of the next block, returns [`None`] once the end of
+    /// the file is reached.
+    ///
+    /// # Arguments
+    /// * `reader` - Reader positioned at the start of a block
+    /// * `flags` - Flags of the file's [`Header`]
+    ///
+    /// # Errors
+    /// Will return [`Err`] if there was an I/O error or the block sizes are inconsistent.
+    pub fn read<I>(reader: &mut I, flags: u32) -> Result<Option<Self>, crate::Error>
+    where
+        I: Read,
+    {
+        let uncompressed_size = reader.read_u32::<BigEndian>()?;
+        if uncompressed_size == 0 {
+            return Ok(None);
+        }
+        if uncompressed_size > MAX_BLOCK_SIZE {
+            return Err(crate::Error::InvalidHeader(
+                "lzop block is larger than the maximum block size",
+            ));
+        }
+        let compressed_size = reader.read_u32::<BigEndian>()?;
+        if compressed_size > uncompressed_size {
+            return Err(crate::Error::InvalidHeader(
+                "lzop block is larger than its uncompressed size",
+            ));
+        }
+
+        let mut read_checksum = |flag: u32, present: bool| -> io::Result<Option<u32>> {
+            if present && flags & flag != 0 {
+                reader.read_u32::<BigEndian>().map(Some)
+            } else {
+                Ok(None)
+            }
+        };
+        let compressed = compressed_size < uncompressed_size;
+
+        Ok(Some(Self {
+            uncompressed_size,
+            compressed_size,
+            uncompressed_adler32: read_checksum(F_ADLER32_D, true)?,
+            uncompressed_crc32: read_checksum(F_CRC32_D, true)?,
+            compressed_adler32: read_checksum(F_ADLER32_C, compressed)?,
+            compressed_crc32: read_checksum(F_CRC32_C, compressed)?,
+        }))
+    }
+
+    /// Returns `true` if the block data is LZO-compressed, `false` if it is stored.
+    #[must_use]
+    pub const fn is_compressed(&self) -> bool {
+        self.compressed_size < self.uncompressed_size
+    }
+
+    fn decompress<I>(&self, reader: &mut I) -> Result<Vec<u8>, crate::Error>
+    where
+        I: Read + Seek,
+    {
+        let data = read_bytes(reader, self.compressed_size as usize)?;
+        if !self.is_compressed() {
+            verify_checksums(&data, self.uncompressed_adler32, self.uncompressed_crc32)?;
+            return Ok(data);
+        }
+
+        verify_checksums(&data, self.compressed_adler32, self.compressed_crc32)?;
+        let expected = self.uncompressed_size as usize;
+        let result = crate::decompress::decompress_all(&data, Some(expected))?;
+        if result.len() != expected {
+            return Err(crate::Error::SizeMismatch {
+                expected,
+                actual: result.len(),
+            });
+        }
+        verify_checksums(&result, self.uncompressed_adler32, self.uncompressed_crc32)?;
+
+        Ok(result)
+    }
+}
+
+fn verify_checksums(data: &[u8], adler: Option<u32>, crc: Option<u32>) -> Result<(), crate::Error> {
+    if adler.is_some_and(|c| c != adler32(1, data)) || crc.is_some_and(|c| c != crc32(0, data)) {
+        return Err(crate::Error::ChecksumMismatch);
+    }
+    Ok(())
+}
+
+impl LzoIndex {
+    /// Generates the index of an lzop file by walking its block headers.
+    ///
+    /// Block data is skipped and not decompressed.
+    ///
+    /// # Errors
+    /// Will return [`Err`] if there was an I/O error or the lzop header is invalid.
+    pub fn build<I>(reader: &mut I) -> Result<Self, crate::Error>
+    where
+        I: Read + Seek,
+    {
+        reader.seek(SeekFrom::Start(0))?;
+        let header = Header::read(reader)?;
+
+        let mut offsets = Vec::new();
+        loop {
+            let offset = reader.stream_position()?;
+            let Some(block) = BlockHeader::read(reader, header.flags)? else {
+                break;
+            };
+            offsets.push(offset);
+            reader.seek(SeekFrom::Current(i64::from(block.compressed_size)))?;
+        }
+
+        Ok(Self { offsets })
+    }
+
+    /// Reads a `.lzo.index` file.
+    ///
+    /// # Errors
+    /// Will return [`Err`] if there was an I/O error or the index is truncated.
+    pub fn read<I>(reader: &mut I) -> Result<Self, crate::Error>
+    where
+        I: Read,
+    {
+        let mut data = Vec::new();
+        reader.read_to_end(&mut data)?;
+
+        let chunks = data.chunks_exact(8);
+        if !chunks.remainder().is_empty() {
+            return Err(crate::Error::InvalidHeader("truncated lzo index"));
+        }
+
+        Ok(Self {
+            offsets: chunks
+                .map(|mut chunk| chunk.read_u64::<BigEndian>())
+                .collect::<io::Result<_>>()?,
+        })
+    }
+
+    /// Writes the index in the `.lzo.index` format.
+    ///
+    /// # Errors
+    /// Will return [`Err`] if there was an I/O error while writing to `writer`.
+    pub fn write<O>(&self, writer: &mut O) -> Result<(), crate::Error>
+    where
+        O: Write,
+    {
+        for offset in &self.offsets {
+            writer.write_u64::<BigEndian>(*offset)?;
+        }
+        Ok(())
+    }
+
+    /// File offsets of all blocks.
+    #[must_use]
+    pub fn offsets(&self) -> &[u64] {
+        &self.offsets
+    }
+
+    /// Number of blocks in the index.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.offsets.len()
+    }
+
+    /// Returns `true` if the index contains no blocks.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.offsets.is_empty()
+    }
+}
+
+/// Decompresses a whole lzop file and returns the result as a new [`Vec<u8>`].
+///
+/// # Errors
+/// Will return [`Err`] if there was an I/O error, the header is invalid or
+/// unsupported, a checksum does not match or a block fails to decompress.
+pub fn decompress<I>(reader: &mut I) -> Result<Vec<u8>, crate::Error>
+where
+    I: Read + Seek,
+{
+    let header = Header::read(reader)?;
+    header.check_supported()?;
+
+    let mut result = Vec::new();
+    while let Some(block) = BlockHeader::read(reader, header.flags)? {
+        result.extend_from_slice(&block.decompress(reader)?);
+    }
+
+    Ok(result)
+}
+
+/// Decompresses a range of blocks of an lzop file, using an [`LzoIndex`] to
+/// seek directly to the first block.
+///
+/// # Arguments
+/// * `reader` - Reader with the lzop file
+/// * `index` - Index of the lzop file
+/// * `blocks` - Range of block numbers to decompress
+///
+/// # Errors
+/// Will return [`Err`] if `blocks` is out of range of `index`, or for any of
+/// the reasons listed in [`decompress`].
+pub fn decompress_blocks<I>(
+    reader: &mut I,
+    index: &LzoIndex,
+    blocks: Range<usize>,
+) -> Result<Vec<u8>, crate::Error>
+where
+    I: Read + Seek,
+{
+    if blocks.start > blocks.end || blocks.end > index.len() {
+        return Err(crate::Error::BlockOutOfRange(blocks.end));
+    }
+
+    reader.seek(SeekFrom::Start(0))?;
+    let header = Header::read(reader)?;
+    header.check_supported()?;
+
+    let mut result = Vec::new();
+    if blocks.is_empty() {
+        return Ok(result);
+    }
+
+    reader.seek(SeekFrom::Start(index.offsets[blocks.start]))?;
+    for i in blocks {
+        let block =
+            BlockHeader::read(reader, header.flags)?.ok_or(crate::Error::BlockOutOfRange(i))?;
+        result.extend_from_slice(&block.decompress(reader)?);
+    }
+
+    Ok(result)
+}
@@ -0,0 +1,185 @@
+//! Packet framing of `OpenVPN`'s legacy `comp-lzo` compression.
+//!
+//! Every packet is prefixed with a marker byte, [`LZO_COMPRESS_BYTE`] for raw
+//! LZO1X data or [`NO_COMPRESS_BYTE`] for the uncompressed payload. Packets
+//! are only compressed if they are at least [`COMPRESS_THRESHOLD`] bytes long
+//! and are sent uncompressed if compression does not make them smaller.
+//!
+//! With `comp-lzo adaptive`, the sender samples how much compression saves
+//! and turns it off for [`AC_OFF_SEC`] seconds if it saves less than
+//! [`AC_SAVE_PCT`] percent.
+//!
+//! ## Example
+//! ```rust
+//! use lzokay_native::openvpn::{self, Compressor};
+//!
+//! fn main() -> Result<(), lzokay_native::Error> {
+//!     let mut compressor = Compressor::new(true);
+//!     let payload = [0u8; 1400];
+//!
+//!     let packet = compressor.compress(&payload)?;
+//!     assert_eq!(packet[0], openvpn::LZO_COMPRESS_BYTE);
+//!     assert_eq!(openvpn::decompress(&packet)?, payload);
+//!
+//!     Ok(())
+//! }
+//! ```
+
+use std::time::{Duration, Instant};
+
+use crate::compress::Dict;
+
+/// Marker of a LZO compressed packet.
+pub const LZO_COMPRESS_BYTE: u8 = 0x66;
+
+/// Marker of an uncompressed packet.
+pub const NO_COMPRESS_BYTE: u8 = 0xfa;
+
+/// Packets smaller than this many bytes are never compressed.
+pub const COMPRESS_THRESHOLD: usize = 100;
+
+/// Length of a sampling period of adaptive compression, in seconds.
+pub const AC_SAMP_SEC: u64 = 2;
+
+/// Minimum number of bytes in a sampling period to turn compression off.
+pub const AC_MIN_BYTES: usize = 1000;
+
+/// Compression is turned off if it saves less than this percentage in a sampling period.
+pub const AC_SAVE_PCT: usize = 5;
+
+/// Number of seconds adaptive compression stays turned off.
+pub const AC_OFF_SEC: u64 = 60;
+
+/// Sampling statistics of adaptive compression.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AdaptiveStats {
+    /// Whether compression is currently turned off
+    pub compression_off: bool,
+    /// End of the current sampling period, or of the off period if compression is off
+    pub next: Instant,
+    /// Number of bytes passed to the compressor in the current sampling period
+    pub n_total: usize,
+    /// Number of bytes the compressor produced in the current sampling period
+    pub n_comp: usize,
+}
+
+/// Compressor of `comp-lzo` packets.
+#[derive(Debug)]
+pub struct Compressor {
+    dict: Dict,
+    adaptive: Option<AdaptiveStats>,
+}
+
+impl AdaptiveStats {
+    const fn new(now: Instant) -> Self {
+        Self {
+            compression_off: false,
+            next: now,
+            n_total: 0,
+            n_comp: 0,
+        }
+    }
+
+    /* Moves to the next period if the current one is over and returns
+     * whether to compress.
+     */
+    fn test(&mut self, now: Instant) -> bool {
+        if now >= self.next {
+            if self.compression_off {
+                self.compression_off = false;
+                self.next = now + Duration::from_secs(AC_SAMP_SEC);
+            } else if self.n_total > AC_MIN_BYTES
+                && self.n_total.saturating_sub(self.n_comp) < self.n_total / (100 / AC_SAVE_PCT)
+            {
+                self.compression_off = true;
+                self.next = now + Duration::from_secs(AC_OFF_SEC);
+            } else {
+                self.next = now + Duration::from_secs(AC_SAMP_SEC);
+            }
+            self.n_total = 0;
+            self.n_comp = 0;
+        }
+        !self.compression_off
+    }
+}
+
+impl Compressor {
+    /// Creates a new compressor, with adaptive compression if `adaptive` is set.
+    #[must_use]
+    pub fn new(adaptive: bool) -> Self {
+        Self {
+            dict: Dict::new(),
+            adaptive: adaptive.then(|| AdaptiveStats::new(Instant::now())),
+        }
+    }
+
+    /// Returns the sampling statistics if adaptive compression is used.
+    #[must_use]
+    pub const fn adaptive(&self) -> Option<&AdaptiveStats> {
+        self.adaptive.as_ref()
+    }
+
+    /// Frames a packet, compressing it if worthwhile, and returns the result as a new [`Vec<u8>`].
+    ///
+    /// # Arguments
+    /// * `payload` - Payload of the packet
+    ///
+    /// # Errors
+    /// Will return [`Err`] if compression fails.
+    pub fn compress(&mut self, payload: &[u8]) -> Result<Vec<u8>, crate::Error> {
+        self.compress_at(payload, Instant::now())
+    }
+
+    /// Frames a packet like [`Compressor::compress`], using `now` as the
+    /// current time for adaptive compression.
+    ///
+    /// # Arguments
+    /// * `payload` - Payload of the packet
+    /// * `now` - Current time
+    ///
+    /// # Errors
+    /// See [`Compressor::compress`] for details on possible errors.
+    pub fn compress_at(&mut self, payload: &[u8], now: Instant) -> Result<Vec<u8>, crate::Error> {
+        if payload.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        if payload.len() >= COMPRESS_THRESHOLD
+            && self.adaptive.as_mut().is_none_or(|ac| ac.test(now))
+        {
+            let compressed = crate::compress::compress_with_dict(payload, &mut self.dict)?;
+            if let Some(ac) = &mut self.adaptive {
+                ac.n_total += payload.len();
+                ac.n_comp += compressed.len();
+            }
+
+            if compressed.len() < payload.len() {
+                let mut result = Vec::with_capacity(compressed.len() + 1);
+                result.push(LZO_COMPRESS_BYTE);
+                result.extend_from_slice(&compressed);
+                return Ok(result);
+            }
+        }
+
+        let mut result = Vec::with_capacity(payload.len() + 1);
+        result.push(NO_COMPRESS_BYTE);
+        result.extend_from_slice(payload);
+        Ok(result)
+    }
+}
+
+/// Unframes a packet, decompressing it if needed, and returns the payload as a new [`Vec<u8>`].
+///
+/// # Arguments
+/// * `packet` - Packet starting with the marker byte
+///
+/// # Errors
+/// Will return [`Err`] if the marker byte is unknown or the packet does not decompress.
+pub fn decompress(packet: &[u8]) -> Result<Vec<u8>, crate::Error> {
+    match packet.split_first() {
+        None => Ok(Vec::new()),
+        Some((&LZO_COMPRESS_BYTE, data)) => crate::decompress::decompress_all(data, None),
+        Some((&NO_COMPRESS_BYTE, data)) => Ok(data.to_vec()),
+        Some(_) => Err(crate::Error::InvalidHeader("unknown comp-lzo marker byte")),
+    }
+}
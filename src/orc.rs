@@ -0,0 +1,132 @@
+//! Decoding and encoding of LZO compressed Apache ORC streams.
+//!
+//! ORC compresses every stream in chunks of at most the compression block
+//! size of the file. Each chunk starts with a 3-byte LE header holding the
+//! chunk length shifted left by one. If the low bit is set, the chunk holds
+//! the original data; otherwise it holds raw LZO1X data.
+//!
+//! ## Example
+//! ```rust
+//! use lzokay_native::orc;
+//!
+//! fn main() -> Result<(), lzokay_native::Error> {
+//!     let data = include_bytes!("../test-data/uncompressed/alice29.txt");
+//!
+//!     let stream = orc::compress(data, orc::DEFAULT_BLOCK_SIZE)?;
+//!     let decompressed = orc::decompress(&stream, orc::DEFAULT_BLOCK_SIZE)?;
+//!     assert_eq!(decompressed, data);
+//!
+//!     Ok(())
+//! }
+//! ```
+
+use byteorder::{ByteOrder, LittleEndian};
+
+use crate::compress::{compress_worst_size, Dict};
+use crate::decompress::decompress_all;
+
+/// Default compression block size of ORC writers.
+pub const DEFAULT_BLOCK_SIZE: usize = 256 * 1024;
+
+/// Largest chunk length the chunk header can hold.
+pub const MAX_CHUNK_LEN: usize = (1 << 23) - 1;
+
+/* Size of the chunk header */
+const HEADER_LEN: usize = 3;
+
+/// Decompresses an ORC stream and returns the result as a new [`Vec<u8>`].
+///
+/// # Arguments
+/// * `data` - Compressed stream
+/// * `block_size` - Compression block size of the file, usually [`DEFAULT_BLOCK_SIZE`]
+///
+/// # Errors
+/// Will return [`Err`] if the stream is truncated, a chunk does not
+/// decompress or a chunk holds more than `block_size` bytes of output.
+pub fn decompress(data: &[u8], block_size: usize) -> Result<Vec<u8>, crate::Error> {
+    let mut result = Vec::new();
+    let mut cur_in = 0;
+    while cur_in < data.len() {
+        if cur_in + HEADER_LEN > data.len() {
+            return Err(crate::Error::InvalidHeader("ORC chunk header is truncated"));
+        }
+        let header = LittleEndian::read_u24(&data[cur_in..]) as usize;
+        cur_in += HEADER_LEN;
+
+        let chunk_len = header >> 1;
+        if chunk_len > data.len() - cur_in {
+            return Err(crate::Error::InvalidHeader("ORC chunk is truncated"));
+        }
+        let chunk = &data[cur_in..cur_in + chunk_len];
+        cur_in += chunk_len;
+
+        let chunk = if header & 1 == 0 {
+            decompress_all(chunk, Some(block_size))?
+        } else {
+            chunk.to_vec()
+        };
+        if chunk.len() > block_size {
+            return Err(crate::Error::OutputOverrun);
+        }
+        result.extend_from_slice(&chunk);
+    }
+
+    Ok(result)
+}
+
+/// Compresses a byte slice into an ORC stream and returns the result as a new [`Vec<u8>`].
+///
+/// Chunks that do not get smaller by compression are stored as original chunks.
+///
+/// # Arguments
+/// * `data` - Data to compress
+/// * `block_size` - Compression block size of the file, usually [`DEFAULT_BLOCK_SIZE`]
+///
+/// # Errors
+/// Will return [`Err`] if compression fails or `block_size` is larger than [`MAX_CHUNK_LEN`].
+pub fn compress(data: &[u8], block_size: usize) -> Result<Vec<u8>, crate::Error> {
+    compress_with_dict(data, block_size, &mut Dict::new())
+}
+
+/// Compresses a byte slice into an ORC stream and returns the result as a
+/// new [`Vec<u8>`], reusing the work memory of `dict`.
+///
+/// # Arguments
+/// * `data` - Data to compress
+/// * `block_size` - Compression block size of the file, usually [`DEFAULT_BLOCK_SIZE`]
+/// * `dict` - Data structure to to store data in
+///
+/// # Errors
+/// See [`compress`] for details on possible errors.
+pub fn compress_with_dict(
+    data: &[u8],
+    block_size: usize,
+    dict: &mut Dict,
+) -> Result<Vec<u8>, crate::Error> {
+    if block_size == 0 || block_size > MAX_CHUNK_LEN {
+        return Err(crate::Error::InvalidHeader(
+            "ORC block size does not fit the chunk header",
+        ));
+    }
+
+    let mut result = Vec::with_capacity(
+        data.len().div_ceil(block_size) * (HEADER_LEN + compress_worst_size(block_size)),
+    );
+
+    for block in data.chunks(block_size) {
+        let chunk = crate::compress::compress_with_dict(block, dict)?;
+
+        let mut header = [0; HEADER_LEN];
+        if chunk.len() < block.len() {
+            LittleEndian::write_u24(&mut header, (chunk.len() << 1) as u32);
+            result.extend_from_slice(&header);
+            result.extend_from_slice(&chunk);
+        } else {
+            LittleEndian::write_u24(&mut header, (block.len() << 1 | 1) as u32);
+            result.extend_from_slice(&header);
+            result.extend_from_slice(block);
+        }
+    }
+
+    Ok(result)
+}
@@ -0,0 +1,337 @@
+//! Reading and writing of Arma PAA textures with LZO compressed mipmaps.
+//!
+//! A PAA texture is made of a type tag, a list of TAGGs, a palette and a
+//! table of mipmaps. DXT mipmaps are LZO1X compressed if the high bit of
+//! their width is set, followed by a 3-byte data size. Their uncompressed
+//! size is computed from width, height and DXT format.
+//!
+//! ## Example
+//! ```rust,no_run
+//! use std::fs::File;
+//!
+//! fn main() -> Result<(), lzokay_native::Error> {
+//!     let mut file = File::open("texture_co.paa")?;
+//!     let paa = lzokay_native::paa::Paa::read(&mut file)?;
+//!
+//!     for mipmap in &paa.mipmaps {
+//!         println!("{}x{}: {} bytes", mipmap.width, mipmap.height, mipmap.data.len());
+//!     }
+//!
+//!     Ok(())
+//! }
+//! ```
+
+use std::borrow::Cow;
+use std::io::{Read, Seek, SeekFrom, Write};
+
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+
+use crate::compress::{compress_with_dict, Dict};
+use crate::decompress::{decompress_all_with_options, DecodeOptions};
+use crate::util::read_bytes;
+
+/* Width flag of LZO compressed mipmaps */
+const LZO_FLAG: u16 = 0x8000;
+
+/// Name of the TAGG holding the file offsets of the mipmaps, as stored.
+pub const OFFS_TAGG: [u8; 4] = *b"SFFO";
+
+/* Number of mipmap offsets in the OFFS TAGG */
+const OFFS_COUNT: usize = 16;
+
+/// DXT mipmaps of at least this many bytes are LZO compressed by [`Paa::write`].
+pub const LZO_MIN_SIZE: usize = 1024;
+
+/// Pixel format of a PAA texture.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PaaType {
+    /// DXT1 / BC1
+    Dxt1,
+    /// DXT2
+    Dxt2,
+    /// DXT3 / BC2
+    Dxt3,
+    /// DXT4
+    Dxt4,
+    /// DXT5 / BC3
+    Dxt5,
+    /// 16 bit RGBA 4:4:4:4
+    Rgba4444,
+    /// 16 bit RGBA 5:5:5:1
+    Rgba5551,
+    /// 32 bit RGBA 8:8:8:8
+    Rgba8888,
+    /// 16 bit gray with alpha
+    GrayAlpha,
+}
+
+/// A TAGG of a PAA texture, holding metadata such as the average color.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Tagg {
+    /// Name of the TAGG as stored, e.g. `b"CGVA"`
+    pub name: [u8; 4],
+    /// Raw data of the TAGG
+    pub data: Vec<u8>,
+}
+
+/// A single mipmap of a PAA texture.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Mipmap {
+    /// Width in pixels, without the LZO flag
+    pub width: u16,
+    /// Height in pixels
+    pub height: u16,
+    /// Whether the mipmap was stored LZO compressed
+    pub lzo_compressed: bool,
+    /// Pixel data; decompressed if the mipmap was LZO compressed, as stored otherwise
+    pub data: Vec<u8>,
+}
+
+/// A PAA texture.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Paa {
+    /// Pixel format of the texture
+    pub paa_type: PaaType,
+    /// TAGGs of the texture
+    pub taggs: Vec<Tagg>,
+    /// Palette of the texture, 3 bytes per entry
+    pub palette: Vec<u8>,
+    /// Mipmaps of the texture, largest first
+    pub mipmaps: Vec<Mipmap>,
+}
+
+impl PaaType {
+    /// Returns the type for a PAA type tag.
+    #[must_use]
+    pub const fn from_tag(tag: u16) -> Option<Self> {
+        match tag {
+            0xff01 => Some(Self::Dxt1),
+            0xff02 => Some(Self::Dxt2),
+            0xff03 => Some(Self::Dxt3),
+            0xff04 => Some(Self::Dxt4),
+            0xff05 => Some(Self::Dxt5),
+            0x4444 => Some(Self::Rgba4444),
+            0x1555 => Some(Self::Rgba5551),
+            0x8888 => Some(Self::Rgba8888),
+            0x8080 => Some(Self::GrayAlpha),
+            _ => None,
+        }
+    }
+
+    /// Returns the PAA type tag of the type.
+    #[must_use]
+    pub const fn tag(self) -> u16 {
+        match self {
+            Self::Dxt1 => 0xff01,
+            Self::Dxt2 => 0xff02,
+            Self::Dxt3 => 0xff03,
+            Self::Dxt4 => 0xff04,
+            Self::Dxt5 => 0xff05,
+            Self::Rgba4444 => 0x4444,
+            Self::Rgba5551 => 0x1555,
+            Self::Rgba8888 => 0x8888,
+            Self::GrayAlpha => 0x8080,
+        }
+    }
+
+    /// Returns `true` for the DXT formats.
+    #[must_use]
+    pub const fn is_dxt(self) -> bool {
+        matches!(
+            self,
+            Self::Dxt1 | Self::Dxt2 | Self::Dxt3 | Self::Dxt4 | Self::Dxt5
+        )
+    }
+
+    /// Returns the uncompressed size of a mipmap of the given dimensions.
+    #[must_use]
+    pub fn mipmap_size(self, width: u16, height: u16) -> usize {
+        let (width, height) = (usize::from(width), usize::from(height));
+        let blocks = width.div_ceil(4).max(1) * height.div_ceil(4).max(1);
+        match self {
+            Self::Dxt1 => blocks * 8,
+            Self::Dxt2 | Self::Dxt3 | Self::Dxt4 | Self::Dxt5 => blocks * 16,
+            Self::Rgba4444 | Self::Rgba5551 | Self::GrayAlpha => width * height * 2,
+            Self::Rgba8888 => width * height * 4,
+        }
+    }
+}
+
+impl Paa {
+    /// Reads a PAA texture and decompresses all of its LZO compressed mipmaps.
+    ///
+    /// Mipmaps which are not LZO compressed are returned as stored; for the
+    /// non-DXT formats this is LZSS compressed data.
+    ///
+    /// # Errors
+    /// Will return [`Err`] if there was an I/O error, the type tag is unknown
+    /// or a mipmap does not decompress to its computed size.
+    pub fn read<I>(reader: &mut I) -> Result<Self, crate::Error>
+    where
+        I: Read + Seek,
+    {
+        let paa_type = PaaType::from_tag(reader.read_u16::<LittleEndian>()?)
+            .ok_or(crate::Error::InvalidHeader("unknown PAA type"))?;
+
+        let mut taggs = Vec::new();
+        loop {
+            let mut signature = [0u8; 4];
+            reader.read_exact(&mut signature)?;
+            if &signature != b"GGAT" {
+                reader.seek(SeekFrom::Current(-4))?;
+                break;
+            }
+            let mut name = [0u8; 4];
+            reader.read_exact(&mut name)?;
+            let len = reader.read_u32::<LittleEndian>()? as usize;
+            taggs.push(Tagg {
+                name,
+                data: read_bytes(reader, len)?,
+            });
+        }
+
+        let palette_len = reader.read_u16::<LittleEndian>()? as usize;
+        let palette = read_bytes(reader, palette_len * 3)?;
+
+        let mut mipmaps = Vec::new();
+        loop {
+            let width = reader.read_u16::<LittleEndian>()?;
+            let height = reader.read_u16::<LittleEndian>()?;
+            if width == 0 && height == 0 {
+                break;
+            }
+            let size = reader.read_u24::<LittleEndian>()? as usize;
+            let data = read_bytes(reader, size)?;
+
+            let lzo_compressed = paa_type.is_dxt() && width & LZO_FLAG != 0;
+            let width = if lzo_compressed {
+                width & !LZO_FLAG
+            } else {
+                width
+            };
+
+            let data = if lzo_compressed {
+                let expected = paa_type.mipmap_size(width, height);
+                decompress_all_with_options(&data, DecodeOptions::new().with_exact_size(expected))?
+            } else {
+                data
+            };
+
+            mipmaps.push(Mipmap {
+                width,
+                height,
+                lzo_compressed,
+                data,
+            });
+        }
+
+        Ok(Self {
+            paa_type,
+            taggs,
+            palette,
+            mipmaps,
+        })
+    }
+
+    /// Writes the texture, LZO compressing every DXT mipmap of at least
+    /// [`LZO_MIN_SIZE`] bytes.
+    ///
+    /// All mipmaps are compressed with one reused [`Dict`]. A mipmap stays
+    /// uncompressed if compression does not make it smaller. Mipmap data of
+    /// non-DXT textures is written as is. An [`OFFS_TAGG`] is rewritten with
+    /// the offsets of the mipmaps as written.
+    ///
+    /// # Errors
+    /// Will return [`Err`] if there was an I/O error while writing to `writer`,
+    /// the palette is not made of 3-byte entries or a mipmap does not fit the
+    /// PAA size fields.
+    pub fn write<O>(&self, writer: &mut O) -> Result<(), crate::Error>
+    where
+        O: Write,
+    {
+        if self.palette.len() % 3 != 0 || self.palette.len() / 3 > usize::from(u16::MAX) {
+            return Err(crate::Error::InvalidHeader(
+                "PAA palette is not made of up to 65535 3-byte entries",
+            ));
+        }
+        if self.mipmaps.len() > OFFS_COUNT && self.taggs.iter().any(|tagg| tagg.name == OFFS_TAGG) {
+            return Err(crate::Error::InvalidHeader(
+                "too many mipmaps for the PAA offsets TAGG",
+            ));
+        }
+
+        let mut dict = Dict::new();
+        let mut mipmaps = Vec::with_capacity(self.mipmaps.len());
+        for mipmap in &self.mipmaps {
+            if mipmap.width & LZO_FLAG != 0 {
+                return Err(crate::Error::InvalidHeader("PAA mipmap is too wide"));
+            }
+            let mut width = mipmap.width;
+            let mut data = Cow::Borrowed(mipmap.data.as_slice());
+            if self.paa_type.is_dxt() && mipmap.data.len() >= LZO_MIN_SIZE {
+                let compressed = compress_with_dict(&mipmap.data, &mut dict)?;
+                if compressed.len() < mipmap.data.len() {
+                    width |= LZO_FLAG;
+                    data = Cow::Owned(compressed);
+                }
+            }
+            if data.len() > 0xff_ffff {
+                return Err(crate::Error::InvalidHeader("PAA mipmap is too large"));
+            }
+            mipmaps.push((width, mipmap.height, data));
+        }
+
+        /* Offsets of the mipmaps following the header */
+        let mut offset = 2
+            + self
+                .taggs
+                .iter()
+                .map(|tagg| {
+                    if tagg.name == OFFS_TAGG {
+                        12 + OFFS_COUNT * 4
+                    } else {
+                        12 + tagg.data.len()
+                    }
+                })
+                .sum::<usize>()
+            + 2
+            + self.palette.len();
+        let mut offsets = Vec::with_capacity(OFFS_COUNT * 4);
+        for (_, _, data) in &mipmaps {
+            let offset_u32 = u32::try_from(offset)
+                .map_err(|_| crate::Error::InvalidHeader("PAA mipmap offset is too large"))?;
+            offsets.write_u32::<LittleEndian>(offset_u32)?;
+            offset += 7 + data.len();
+        }
+        offsets.resize(OFFS_COUNT * 4, 0);
+
+        writer.write_u16::<LittleEndian>(self.paa_type.tag())?;
+
+        for tagg in &self.taggs {
+            let data = if tagg.name == OFFS_TAGG {
+                &offsets
+            } else {
+                &tagg.data
+            };
+            writer.write_all(b"GGAT")?;
+            writer.write_all(&tagg.name)?;
+            writer.write_u32::<LittleEndian>(data.len() as u32)?;
+            writer.write_all(data)?;
+        }
+
+        writer.write_u16::<LittleEndian>((self.palette.len() / 3) as u16)?;
+        writer.write_all(&self.palette)?;
+
+        for (width, height, data) in &mipmaps {
+            writer.write_u16::<LittleEndian>(*width)?;
+            writer.write_u16::<LittleEndian>(*height)?;
+            writer.write_u24::<LittleEndian>(data.len() as u32)?;
+            writer.write_all(data)?;
+        }
+
+        /* End of mipmaps */
+        writer.write_all(&[0; 6])?;
+
+        Ok(())
+    }
+}
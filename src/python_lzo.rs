@@ -0,0 +1,89 @@
+//! Support for the header format of `python-lzo`.
+//!
+//! By default, `lzo.compress` of `python-lzo` prepends a 5-byte header to the
+//! raw LZO1X data: a method byte, [`LZO1X_1`] or [`LZO1X_999`], followed by
+//! the uncompressed length as BE32. Both methods produce the same bitstream.
+//!
+//! ## Example
+//! ```rust
+//! fn main() -> Result<(), lzokay_native::Error> {
+//!     let data = include_bytes!("../test-data/uncompressed/alice29.txt");
+//!
+//!     let compressed = lzokay_native::python_lzo::compress(data)?;
+//!     let decompressed = lzokay_native::python_lzo::decompress(&compressed)?;
+//!     assert_eq!(decompressed, data);
+//!
+//!     Ok(())
+//! }
+//! ```
+
+use byteorder::{BigEndian, ByteOrder};
+
+use crate::compress::Dict;
+use crate::decompress::{decompress_all_with_options, DecodeOptions};
+
+/// Method byte of data compressed with LZO1X-1, used by `python-lzo` for level 1.
+pub const LZO1X_1: u8 = 0xf0;
+
+/// Method byte of data compressed with LZO1X-999, used by `python-lzo` for
+/// higher levels and written by [`compress`].
+pub const LZO1X_999: u8 = 0xf1;
+
+/* Size of the method byte and the uncompressed length */
+const HEADER_LEN: usize = 5;
+
+/// Compresses a byte slice and returns it with a `python-lzo` header as a new [`Vec<u8>`].
+///
+/// # Arguments
+/// * `data` - Data to compress
+///
+/// # Errors
+/// Will return [`Err`] if compression fails or `data` is larger than 4 GiB.
+pub fn compress(data: &[u8]) -> Result<Vec<u8>, crate::Error> {
+    compress_with_dict(data, &mut Dict::new())
+}
+
+/// Compresses a byte slice and returns it with a `python-lzo` header as a
+/// new [`Vec<u8>`], reusing the work memory of `dict`.
+///
+/// # Arguments
+/// * `data` - Data to compress
+/// * `dict` - Data structure to to store data in
+///
+/// # Errors
+/// See [`compress`] for details on possible errors.
+pub fn compress_with_dict(data: &[u8], dict: &mut Dict) -> Result<Vec<u8>, crate::Error> {
+    let len = u32::try_from(data.len())
+        .map_err(|_| crate::Error::InvalidHeader("data is too large for a python-lzo header"))?;
+
+    let mut result = vec![LZO1X_999, 0, 0, 0, 0];
+    BigEndian::write_u32(&mut result[1..], len);
+    if data.is_empty() {
+        /* python-lzo always writes a stream, even a bare terminating M4 */
+        result.extend_from_slice(&[0x11, 0, 0]);
+    } else {
+        result.extend_from_slice(&crate::compress::compress_with_dict(data, dict)?);
+    }
+
+    Ok(result)
+}
+
+/// Decompresses data with a `python-lzo` header and returns the result as a new [`Vec<u8>`].
+///
+/// # Arguments
+/// * `data` - Data to decompress, starting with the header
+///
+/// # Errors
+/// Will return [`Err`] if the header is invalid, the data does not
+/// decompress or it does not decompress to the length stored in the header.
+pub fn decompress(data: &[u8]) -> Result<Vec<u8>, crate::Error> {
+    if data.len() < HEADER_LEN || !matches!(data[0], LZO1X_1 | LZO1X_999) {
+        return Err(crate::Error::InvalidHeader("not a python-lzo header"));
+    }
+    let expected = BigEndian::read_u32(&data[1..]) as usize;
+
+    decompress_all_with_options(
+        &data[HEADER_LEN..],
+        DecodeOptions::new().with_exact_size(expected),
+    )
+}
@@ -0,0 +1,132 @@
+//! Decoding and encoding of the segmented LZO resources of Retro Studios
+//! games, such as the compressed resources of Metroid Prime PAK files.
+//!
+//! The data is split into segments of at most [`SEGMENT_SIZE`] bytes of
+//! output. Each segment starts with a signed BE16 size. A positive size is
+//! followed by that many bytes of raw LZO1X data, a negative or zero size by
+//! `-size` bytes of stored data. Segments carry no terminator, so the total
+//! uncompressed size has to be known when decoding.
+//!
+//! ## Example
+//! ```rust
+//! use lzokay_native::retro;
+//!
+//! fn main() -> Result<(), lzokay_native::Error> {
+//!     let data = include_bytes!("../test-data/uncompressed/alice29.txt");
+//!
+//!     let compressed = retro::compress(data)?;
+//!     let decompressed = retro::decompress(&compressed, data.len())?;
+//!     assert_eq!(decompressed, data);
+//!
+//!     Ok(())
+//! }
+//! ```
+
+use byteorder::{BigEndian, ByteOrder};
+
+use crate::compress::{compress_worst_size, Dict};
+use crate::decompress::decompress_all;
+
+/// Maximum number of uncompressed bytes in a segment.
+pub const SEGMENT_SIZE: usize = 0x4000;
+
+/* Size of the segment header */
+const HEADER_LEN: usize = 2;
+
+/// Decompresses segmented data and returns the result as a new [`Vec<u8>`].
+///
+/// Segments are decoded until `size` bytes of output are produced. Data
+/// following the last segment is ignored.
+///
+/// # Arguments
+/// * `data` - Segmented data
+/// * `size` - Total uncompressed size of the data
+///
+/// # Errors
+/// Will return [`Err`] if the data is truncated, a segment does not
+/// decompress, a segment decompresses to more than [`SEGMENT_SIZE`] bytes or
+/// the segments do not decompress to exactly `size` bytes.
+pub fn decompress(data: &[u8], size: usize) -> Result<Vec<u8>, crate::Error> {
+    let mut result = Vec::with_capacity(size);
+    let mut cur_in = 0;
+    while result.len() < size {
+        if cur_in + HEADER_LEN > data.len() {
+            return Err(crate::Error::InvalidHeader(
+                "retro segment header is truncated",
+            ));
+        }
+        let seg_size = BigEndian::read_i16(&data[cur_in..]);
+        cur_in += HEADER_LEN;
+
+        let seg_len = seg_size.unsigned_abs() as usize;
+        if seg_len > data.len() - cur_in {
+            return Err(crate::Error::InvalidHeader("retro segment is truncated"));
+        }
+        let segment = &data[cur_in..cur_in + seg_len];
+        cur_in += seg_len;
+
+        if seg_size > 0 {
+            let segment = decompress_all(segment, Some(SEGMENT_SIZE))?;
+            if segment.len() > SEGMENT_SIZE {
+                return Err(crate::Error::OutputOverrun);
+            }
+            result.extend_from_slice(&segment);
+        } else {
+            result.extend_from_slice(segment);
+        }
+    }
+
+    if result.len() != size {
+        return Err(crate::Error::SizeMismatch {
+            expected: size,
+            actual: result.len(),
+        });
+    }
+
+    Ok(result)
+}
+
+/// Compresses a byte slice into segmented data and returns the result as a new [`Vec<u8>`].
+///
+/// Segments that do not get smaller by compression are stored.
+///
+/// # Arguments
+/// * `data` - Data to compress
+///
+/// # Errors
+/// Will return [`Err`] if compression fails.
+pub fn compress(data: &[u8]) -> Result<Vec<u8>, crate::Error> {
+    compress_with_dict(data, &mut Dict::new())
+}
+
+/// Compresses a byte slice into segmented data and returns the result as a
+/// new [`Vec<u8>`], reusing the work memory of `dict`.
+///
+/// # Arguments
+/// * `data` - Data to compress
+/// * `dict` - Data structure to to store data in
+///
+/// # Errors
+/// See [`compress`] for details on possible errors.
+pub fn compress_with_dict(data: &[u8], dict: &mut Dict) -> Result<Vec<u8>, crate::Error> {
+    let mut result = Vec::with_capacity(
+        data.len().div_ceil(SEGMENT_SIZE) * (HEADER_LEN + compress_worst_size(SEGMENT_SIZE)),
+    );
+
+    for chunk in data.chunks(SEGMENT_SIZE) {
+        let segment = crate::compress::compress_with_dict(chunk, dict)?;
+
+        let mut header = [0; HEADER_LEN];
+        if segment.len() < chunk.len() {
+            BigEndian::write_i16(&mut header, segment.len() as i16);
+            result.extend_from_slice(&header);
+            result.extend_from_slice(&segment);
+        } else {
+            BigEndian::write_i16(&mut header, -(chunk.len() as i16));
+            result.extend_from_slice(&header);
+            result.extend_from_slice(chunk);
+        }
+    }
+
+    Ok(result)
+}
@@ -0,0 +1,282 @@
+//! A seekable container of independently compressed LZO1X blocks.
+//!
+//! The data is split into blocks of a fixed uncompressed size, each
+//! compressed into a raw LZO1X stream. The blocks are followed by an index
+//! with one entry per block, made of the LE32 compressed size, uncompressed
+//! size and CRC-32 of the uncompressed data, and a footer made of the LE32
+//! number of blocks and [`MAGIC`].
+//!
+//! [`SeekableReader`] reads the index and only decompresses the blocks
+//! covering the bytes that are read.
+//!
+//! ## Example
+//! ```rust
+//! use std::io::{Cursor, Read, Seek, SeekFrom};
+//! use lzokay_native::seekable::{self, SeekableReader};
+//!
+//! fn main() -> Result<(), lzokay_native::Error> {
+//!     let data = include_bytes!("../test-data/uncompressed/alice29.txt");
+//!     let container = seekable::compress(data, seekable::DEFAULT_BLOCK_SIZE)?;
+//!
+//!     let mut reader = SeekableReader::new(Cursor::new(container))?;
+//!     reader.seek(SeekFrom::Start(100_000))?;
+//!     let mut buf = [0u8; 16];
+//!     reader.read_exact(&mut buf)?;
+//!     assert_eq!(buf, data[100_000..100_016]);
+//!
+//!     Ok(())
+//! }
+//! ```
+
+use std::io::{self, Read, Seek, SeekFrom};
+
+use byteorder::{ByteOrder, LittleEndian, ReadBytesExt};
+
+use crate::compress::{compress_worst_size, Dict};
+use crate::decompress::{decompress_all_with_options, DecodeOptions};
+use crate::util::{crc32, read_bytes};
+
+/// Magic at the end of a seekable container.
+pub const MAGIC: [u8; 4] = *b"LZSK";
+
+/// Default uncompressed size of a block.
+pub const DEFAULT_BLOCK_SIZE: usize = 64 * 1024;
+
+/* Size of an index entry and of the footer */
+const ENTRY_LEN: usize = 12;
+const FOOTER_LEN: usize = 8;
+
+/* Index entry of a block, with the offsets of the block in the container and the data */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Block {
+    offset: u64,
+    compressed_len: usize,
+    start: u64,
+    len: usize,
+    checksum: u32,
+}
+
+/// Compresses a byte slice into a seekable container and returns the result as a new [`Vec<u8>`].
+///
+/// # Arguments
+/// * `data` - Data to compress
+/// * `block_size` - Uncompressed size of a block, usually [`DEFAULT_BLOCK_SIZE`]
+///
+/// # Errors
+/// Will return [`Err`] if compression fails or `block_size` is 0 or larger than 4 GiB.
+pub fn compress(data: &[u8], block_size: usize) -> Result<Vec<u8>, crate::Error> {
+    compress_with_dict(data, block_size, &mut Dict::new())
+}
+
+/// Compresses a byte slice into a seekable container and returns the result
+/// as a new [`Vec<u8>`], reusing the work memory of `dict`.
+///
+/// # Arguments
+/// * `data` - Data to compress
+/// * `block_size` - Uncompressed size of a block, usually [`DEFAULT_BLOCK_SIZE`]
+/// * `dict` - Data structure to to store data in
+///
+/// # Errors
+/// See [`compress`] for details on possible errors.
+pub fn compress_with_dict(
+    data: &[u8],
+    block_size: usize,
+    dict: &mut Dict,
+) -> Result<Vec<u8>, crate::Error> {
+    if block_size == 0 || u32::try_from(compress_worst_size(block_size)).is_err() {
+        return Err(crate::Error::InvalidHeader(
+            "block size does not fit the seekable index",
+        ));
+    }
+    let blocks = data.chunks(block_size);
+    let count = u32::try_from(blocks.len())
+        .map_err(|_| crate::Error::InvalidHeader("too many blocks for the seekable index"))?;
+
+    let mut result = Vec::new();
+    let mut index = Vec::with_capacity(blocks.len() * ENTRY_LEN + FOOTER_LEN);
+    let mut entry = [0; ENTRY_LEN];
+    for block in blocks {
+        let compressed = crate::compress::compress_with_dict(block, dict)?;
+        result.extend_from_slice(&compressed);
+
+        LittleEndian::write_u32(&mut entry, compressed.len() as u32);
+        LittleEndian::write_u32(&mut entry[4..], block.len() as u32);
+        LittleEndian::write_u32(&mut entry[8..], crc32(0, block));
+        index.extend_from_slice(&entry);
+    }
+
+    result.extend_from_slice(&index);
+    result.extend_from_slice(&count.to_le_bytes());
+    result.extend_from_slice(&MAGIC);
+
+    Ok(result)
+}
+
+/// Reader of a seekable container, decompressing only the blocks covering
+/// the bytes read.
+///
+/// The last decompressed block is kept, so small sequential reads don't
+/// decompress a block more than once.
+#[derive(Debug)]
+pub struct SeekableReader<R> {
+    inner: R,
+    blocks: Vec<Block>,
+    len: u64,
+    pos: u64,
+    cache: Option<(usize, Vec<u8>)>,
+}
+
+impl<R> SeekableReader<R>
+where
+    R: Read + Seek,
+{
+    /// Creates a reader of the container in `inner`, reading its index.
+    ///
+    /// # Errors
+    /// Will return [`Err`] if there was an I/O error or the footer or index is invalid.
+    pub fn new(mut inner: R) -> Result<Self, crate::Error> {
+        let container_len = inner.seek(SeekFrom::End(0))?;
+        if container_len < FOOTER_LEN as u64 {
+            return Err(crate::Error::InvalidHeader(
+                "seekable container is truncated",
+            ));
+        }
+        inner.seek(SeekFrom::End(-(FOOTER_LEN as i64)))?;
+        let count = u64::from(inner.read_u32::<LittleEndian>()?);
+        let mut magic = [0; 4];
+        inner.read_exact(&mut magic)?;
+        if magic != MAGIC {
+            return Err(crate::Error::InvalidHeader("not a seekable container"));
+        }
+
+        let index_len = count * ENTRY_LEN as u64;
+        let Some(index_start) = (container_len - FOOTER_LEN as u64).checked_sub(index_len) else {
+            return Err(crate::Error::InvalidHeader("seekable index is truncated"));
+        };
+        inner.seek(SeekFrom::Start(index_start))?;
+        let index = read_bytes(&mut inner, index_len as usize)?;
+
+        let mut blocks = Vec::with_capacity(count as usize);
+        let (mut offset, mut start) = (0, 0);
+        for entry in index.chunks(ENTRY_LEN) {
+            let block = Block {
+                offset,
+                compressed_len: LittleEndian::read_u32(entry) as usize,
+                start,
+                len: LittleEndian::read_u32(&entry[4..]) as usize,
+                checksum: LittleEndian::read_u32(&entry[8..]),
+            };
+            offset += block.compressed_len as u64;
+            start += block.len as u64;
+            blocks.push(block);
+        }
+        if offset != index_start {
+            return Err(crate::Error::InvalidHeader(
+                "seekable index does not match the blocks",
+            ));
+        }
+
+        Ok(Self {
+            inner,
+            blocks,
+            len: start,
+            pos: 0,
+            cache: None,
+        })
+    }
+
+    /// Returns the uncompressed size of the data.
+    #[must_use]
+    pub const fn len(&self) -> u64 {
+        self.len
+    }
+
+    /// Returns `true` if the container holds no data.
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the number of blocks of the container.
+    #[must_use]
+    pub fn block_count(&self) -> usize {
+        self.blocks.len()
+    }
+
+    /// Returns the underlying reader.
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+
+    /* Decompresses and verifies a block, unless it is the cached one */
+    fn load(&mut self, index: usize) -> Result<&[u8], crate::Error> {
+        if self
+            .cache
+            .as_ref()
+            .is_none_or(|(cached, _)| *cached != index)
+        {
+            let block = self.blocks[index];
+            self.inner.seek(SeekFrom::Start(block.offset))?;
+            let compressed = read_bytes(&mut self.inner, block.compressed_len)?;
+
+            let data = decompress_all_with_options(
+                &compressed,
+                DecodeOptions::new().with_exact_size(block.len),
+            )?;
+            if crc32(0, &data) != block.checksum {
+                return Err(crate::Error::ChecksumMismatch);
+            }
+            self.cache = Some((index, data));
+        }
+
+        Ok(self.cache.as_ref().map_or(&[], |(_, data)| data))
+    }
+}
+
+impl<R> Read for SeekableReader<R>
+where
+    R: Read + Seek,
+{
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() || self.pos >= self.len {
+            return Ok(0);
+        }
+
+        let pos = self.pos;
+        let index = self
+            .blocks
+            .partition_point(|block| block.start + block.len as u64 <= pos);
+        let offset = (pos - self.blocks[index].start) as usize;
+        let data = self.load(index).map_err(|err| match err {
+            crate::Error::IOError(err) => err,
+            err => io::Error::new(io::ErrorKind::InvalidData, err),
+        })?;
+
+        let len = buf.len().min(data.len() - offset);
+        buf[..len].copy_from_slice(&data[offset..offset + len]);
+        self.pos += len as u64;
+
+        Ok(len)
+    }
+}
+
+impl<R> Seek for SeekableReader<R>
+where
+    R: Read + Seek,
+{
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let pos = match pos {
+            SeekFrom::Start(pos) => Some(pos),
+            SeekFrom::End(delta) => self.len.checked_add_signed(delta),
+            SeekFrom::Current(delta) => self.pos.checked_add_signed(delta),
+        };
+
+        self.pos = pos.ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "invalid seek to a negative or overflowing position",
+            )
+        })?;
+        Ok(self.pos)
+    }
+}
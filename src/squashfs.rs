@@ -0,0 +1,278 @@
+//! Support for `SquashFS` images compressed with `-comp lzo`.
+//!
+//! Data blocks and fragments are stored as raw LZO1X data, or uncompressed
+//! if [`DATA_UNCOMPRESSED`] is set in their size field. Metadata blocks start
+//! with a LE16 header holding the size of the block, with
+//! [`METADATA_UNCOMPRESSED`] set if the block is stored uncompressed.
+//!
+//! Images may record the LZO algorithm and level used by `mksquashfs` in a
+//! [`CompOpts`] record, stored in a metadata block after the superblock.
+//!
+//! ## Example
+//! ```rust
+//! use std::io::Cursor;
+//! use lzokay_native::squashfs::{self, CompOpts};
+//!
+//! fn main() -> Result<(), lzokay_native::Error> {
+//!     let opts = CompOpts::default();
+//!     let mut dict = lzokay_native::Dict::new();
+//!     let inodes = [0u8; 4096];
+//!
+//!     let mut image = Vec::new();
+//!     squashfs::write_metadata_block(&mut image, &inodes, opts, &mut dict)?;
+//!
+//!     let block = squashfs::read_metadata_block(&mut Cursor::new(image))?;
+//!     assert_eq!(block, inodes);
+//!
+//!     Ok(())
+//! }
+//! ```
+
+use std::io::{Read, Write};
+
+use byteorder::{ByteOrder, LittleEndian, ReadBytesExt, WriteBytesExt};
+
+use crate::compress::Dict;
+use crate::decompress::decompress_all;
+use crate::util::read_bytes;
+
+/// Maximum number of uncompressed bytes in a metadata block.
+pub const METADATA_SIZE: usize = 8192;
+
+/// Flag of the metadata block header marking an uncompressed block.
+pub const METADATA_UNCOMPRESSED: u16 = 0x8000;
+
+/// Flag of the size field of data blocks and fragments marking an uncompressed block.
+pub const DATA_UNCOMPRESSED: u32 = 1 << 24;
+
+/// Compression level of [`Algorithm::Lzo1x999`] used if none is recorded.
+pub const LZO1X_999_DEFAULT_LEVEL: u32 = 8;
+
+/// LZO algorithm recorded in the compression options of an image.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Algorithm {
+    /// `lzo1x_1`
+    Lzo1x1,
+    /// `lzo1x_1_11`
+    Lzo1x1_11,
+    /// `lzo1x_1_12`
+    Lzo1x1_12,
+    /// `lzo1x_1_15`
+    Lzo1x1_15,
+    /// `lzo1x_999`, the default of `mksquashfs`
+    Lzo1x999,
+}
+
+/// Compression options record of an LZO compressed image.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CompOpts {
+    /// Algorithm used to compress the image
+    pub algorithm: Algorithm,
+    /// Compression level, 1 to 9 for [`Algorithm::Lzo1x999`] and 0 otherwise
+    pub level: u32,
+}
+
+impl Algorithm {
+    /// Returns the algorithm for its id in the compression options record.
+    #[must_use]
+    pub const fn from_id(id: u32) -> Option<Self> {
+        match id {
+            0 => Some(Self::Lzo1x1),
+            1 => Some(Self::Lzo1x1_11),
+            2 => Some(Self::Lzo1x1_12),
+            3 => Some(Self::Lzo1x1_15),
+            4 => Some(Self::Lzo1x999),
+            _ => None,
+        }
+    }
+
+    /// Returns the id of the algorithm in the compression options record.
+    #[must_use]
+    pub const fn id(self) -> u32 {
+        match self {
+            Self::Lzo1x1 => 0,
+            Self::Lzo1x1_11 => 1,
+            Self::Lzo1x1_12 => 2,
+            Self::Lzo1x1_15 => 3,
+            Self::Lzo1x999 => 4,
+        }
+    }
+}
+
+impl Default for CompOpts {
+    /// Returns the options `mksquashfs` uses if none are given.
+    fn default() -> Self {
+        Self {
+            algorithm: Algorithm::Lzo1x999,
+            level: LZO1X_999_DEFAULT_LEVEL,
+        }
+    }
+}
+
+impl CompOpts {
+    /// Size of the compression options record.
+    pub const SIZE: usize = 8;
+
+    /// Parses a compression options record, as read from its metadata block.
+    ///
+    /// # Errors
+    /// Will return [`Err`] if the record is truncated, the algorithm is
+    /// unknown or the level is invalid for the algorithm.
+    pub fn read(data: &[u8]) -> Result<Self, crate::Error> {
+        if data.len() < Self::SIZE {
+            return Err(crate::Error::InvalidHeader(
+                "squashfs compression options are truncated",
+            ));
+        }
+        let algorithm = Algorithm::from_id(LittleEndian::read_u32(data)).ok_or(
+            crate::Error::InvalidHeader("unknown squashfs LZO algorithm"),
+        )?;
+        let level = LittleEndian::read_u32(&data[4..]);
+
+        let valid = match algorithm {
+            Algorithm::Lzo1x999 => (1..=9).contains(&level),
+            _ => level == 0,
+        };
+        if !valid {
+            return Err(crate::Error::InvalidHeader(
+                "invalid squashfs LZO compression level",
+            ));
+        }
+
+        Ok(Self { algorithm, level })
+    }
+
+    /// Returns the compression options record.
+    #[must_use]
+    pub fn to_bytes(self) -> [u8; Self::SIZE] {
+        let mut result = [0; Self::SIZE];
+        LittleEndian::write_u32(&mut result, self.algorithm.id());
+        LittleEndian::write_u32(&mut result[4..], self.level);
+        result
+    }
+
+    /// Returns `true` if this crate's compressor matches the recorded algorithm.
+    ///
+    /// The compressor works like `lzo1x_999` and has no levels, so only
+    /// [`Algorithm::Lzo1x999`] is supported. Images using the other
+    /// algorithms can still be read.
+    #[must_use]
+    pub const fn is_supported(self) -> bool {
+        matches!(self.algorithm, Algorithm::Lzo1x999)
+    }
+
+    const fn check_supported(self) -> Result<(), crate::Error> {
+        if !self.is_supported() {
+            return Err(crate::Error::InvalidHeader(
+                "unsupported squashfs LZO algorithm",
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Decompresses a data block or fragment and returns the result as a new [`Vec<u8>`].
+///
+/// Blocks with [`DATA_UNCOMPRESSED`] set in their size field are stored
+/// as is and must not be passed to this function.
+///
+/// # Arguments
+/// * `data` - Compressed block
+/// * `block_size` - Block size of the image
+///
+/// # Errors
+/// Will return [`Err`] if the block does not decompress or holds more than `block_size` bytes.
+pub fn decompress_block(data: &[u8], block_size: usize) -> Result<Vec<u8>, crate::Error> {
+    let result = decompress_all(data, Some(block_size))?;
+    if result.len() > block_size {
+        return Err(crate::Error::OutputOverrun);
+    }
+
+    Ok(result)
+}
+
+/// Compresses a data block or fragment with the recorded algorithm.
+///
+/// Returns [`None`] if compression does not make the block smaller, in
+/// which case it is stored uncompressed.
+///
+/// # Arguments
+/// * `data` - Data of the block
+/// * `opts` - Compression options of the image
+/// * `dict` - Data structure to to store data in
+///
+/// # Errors
+/// Will return [`Err`] if compression fails or the algorithm is not supported, see [`CompOpts::is_supported`].
+pub fn compress_block(
+    data: &[u8],
+    opts: CompOpts,
+    dict: &mut Dict,
+) -> Result<Option<Vec<u8>>, crate::Error> {
+    opts.check_supported()?;
+
+    let compressed = crate::compress::compress_with_dict(data, dict)?;
+    Ok((compressed.len() < data.len()).then_some(compressed))
+}
+
+/// Reads a metadata block and returns its decompressed data as a new [`Vec<u8>`].
+///
+/// # Arguments
+/// * `reader` - Reader positioned at the header of the block
+///
+/// # Errors
+/// Will return [`Err`] if there was an I/O error, the block does not
+/// decompress or it holds more than [`METADATA_SIZE`] bytes.
+pub fn read_metadata_block<I>(reader: &mut I) -> Result<Vec<u8>, crate::Error>
+where
+    I: Read,
+{
+    let header = reader.read_u16::<LittleEndian>()?;
+    let data = read_bytes(reader, usize::from(header & !METADATA_UNCOMPRESSED))?;
+
+    let result = if header & METADATA_UNCOMPRESSED == 0 {
+        decompress_all(&data, Some(METADATA_SIZE))?
+    } else {
+        data
+    };
+    if result.len() > METADATA_SIZE {
+        return Err(crate::Error::OutputOverrun);
+    }
+
+    Ok(result)
+}
+
+/// Writes a metadata block, compressing it with the recorded algorithm if
+/// that makes it smaller.
+///
+/// # Arguments
+/// * `writer` - Writer to write the block to
+/// * `data` - Data of the block, at most [`METADATA_SIZE`] bytes
+/// * `opts` - Compression options of the image
+/// * `dict` - Data structure to to store data in
+///
+/// # Errors
+/// Will return [`Err`] if `data` is too large, compression fails, the
+/// algorithm is not supported or there was an I/O error while writing to `writer`.
+pub fn write_metadata_block<O>(
+    writer: &mut O,
+    data: &[u8],
+    opts: CompOpts,
+    dict: &mut Dict,
+) -> Result<(), crate::Error>
+where
+    O: Write,
+{
+    if data.len() > METADATA_SIZE {
+        return Err(crate::Error::OutputOverrun);
+    }
+
+    if let Some(compressed) = compress_block(data, opts, dict)? {
+        writer.write_u16::<LittleEndian>(compressed.len() as u16)?;
+        writer.write_all(&compressed)?;
+    } else {
+        writer.write_u16::<LittleEndian>(data.len() as u16 | METADATA_UNCOMPRESSED)?;
+        writer.write_all(data)?;
+    }
+
+    Ok(())
+}
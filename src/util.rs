@@ -1,7 +1,16 @@
-#[cfg(feature = "decompress")]
-use std::io::{self, Read, Seek, SeekFrom};
+#[cfg(any(
+    feature = "lzop",
+    feature = "lzo-rle",
+    feature = "paa",
+    feature = "squashfs",
+    feature = "framing",
+    feature = "seekable"
+))]
+use std::io::{self, Read};
+#[cfg(feature = "lzo-rle")]
+use std::io::{Seek, SeekFrom};
 
-#[cfg(feature = "decompress")]
+#[cfg(feature = "lzo-rle")]
 use byteorder::ReadBytesExt;
 
 /// The various errors that can be reported by this crate.
@@ -15,9 +24,51 @@ pub enum Error {
     #[error("Output overrun")]
     OutputOverrun,
 
-    /// IOError
+    /// `IOError`
     #[error("read or write failed, source: {0}")]
     IOError(#[from] std::io::Error),
+
+    /// Invalid or unsupported container header
+    #[error("Invalid header: {0}")]
+    InvalidHeader(&'static str),
+
+    /// Checksum mismatch
+    #[error("Checksum mismatch")]
+    ChecksumMismatch,
+
+    /// Decompressed size differs from the size declared by a header
+    #[error("Expected {expected} bytes of output, got {actual}")]
+    SizeMismatch {
+        /// Size declared by the header
+        expected: usize,
+        /// Size actually produced
+        actual: usize,
+    },
+
+    /// Lookbehind overrun, a match refers to data before the start of the output
+    #[error("Lookbehind overrun")]
+    LookbehindOverrun,
+
+    /// Block index out of range
+    #[error("Block index {0} out of range")]
+    BlockOutOfRange(usize),
+
+    /// Requested range of the output is reversed or ends past the output
+    #[error("Range {start}..{end} out of bounds")]
+    RangeOutOfBounds {
+        /// Start of the requested range
+        start: u64,
+        /// End of the requested range
+        end: u64,
+    },
+
+    /// Data following the end of a stream
+    #[error("Trailing data after the end of the stream")]
+    TrailingData,
+
+    /// Instruction that can't be encoded in a LZO1X stream
+    #[error("Invalid instruction: {0}")]
+    InvalidInstruction(&'static str),
 }
 
 // pub(crate) static mut MAX_255_COUNT: usize = ((!0) as usize / 255 - 2) as usize;
@@ -37,12 +88,12 @@ pub static mut M3_MAX_LEN: u32 = 33;
 pub static mut M4_MAX_LEN: u32 = 9;
 #[cfg(feature = "compress")]
 pub static mut M1_MARKER: u32 = 0;
-#[cfg(any(feature = "compress", feature = "decompress"))]
+#[cfg(any(feature = "compress", feature = "decompress", feature = "writer"))]
 pub const M3_MARKER: u32 = 0x20;
-#[cfg(any(feature = "compress", feature = "decompress"))]
+#[cfg(any(feature = "compress", feature = "decompress", feature = "writer"))]
 pub const M4_MARKER: u32 = 0x10;
 
-#[cfg(feature = "decompress")]
+#[cfg(feature = "lzo-rle")]
 pub fn peek_u8<I>(reader: &mut I) -> io::Result<u8>
 where
     I: Read + Seek,
@@ -53,28 +104,55 @@ where
     Ok(ret)
 }
 
-#[cfg(feature = "decompress")]
+#[cfg(any(
+    feature = "lzop",
+    feature = "paa",
+    feature = "squashfs",
+    feature = "framing",
+    feature = "seekable"
+))]
 pub fn read_bytes<I>(reader: &mut I, size: usize) -> io::Result<Vec<u8>>
 where
-    I: Read + Seek,
+    I: Read,
 {
-    let mut buf = vec![0u8; size];
-    reader.read_exact(&mut buf)?;
+    /* `size` often comes from a header, so only allocate what is actually read */
+    let mut buf = Vec::new();
+    if (&mut *reader).take(size as u64).read_to_end(&mut buf)? != size {
+        return Err(io::Error::from(io::ErrorKind::UnexpectedEof));
+    }
     Ok(buf)
 }
 
-#[cfg(feature = "decompress")]
-pub fn consume_zero_byte_length_stream<I>(reader: &mut I) -> Result<u64, crate::Error>
-where
-    I: Read + Seek,
-{
-    let old_pos = reader.stream_position()?;
+#[cfg(feature = "lzop")]
+pub fn adler32(adler: u32, data: &[u8]) -> u32 {
+    const BASE: u32 = 65521;
+    const NMAX: usize = 5552;
 
-    while peek_u8(reader)? == 0 {
-        reader.seek(SeekFrom::Current(1))?;
+    let mut s1 = adler & 0xffff;
+    let mut s2 = adler >> 16;
+    for chunk in data.chunks(NMAX) {
+        for &b in chunk {
+            s1 += u32::from(b);
+            s2 += s1;
+        }
+        s1 %= BASE;
+        s2 %= BASE;
     }
+    (s2 << 16) | s1
+}
 
-    let offset = reader.stream_position()? - old_pos;
-
-    Ok(offset)
+#[cfg(any(feature = "lzop", feature = "seekable"))]
+pub fn crc32(crc: u32, data: &[u8]) -> u32 {
+    let mut c = !crc;
+    for &b in data {
+        c ^= u32::from(b);
+        for _ in 0..8 {
+            c = if c & 1 == 0 {
+                c >> 1
+            } else {
+                (c >> 1) ^ 0xedb8_8320
+            };
+        }
+    }
+    !c
 }
@@ -0,0 +1,216 @@
+//! Serialization of custom LZ77 parses into raw LZO1X streams.
+//!
+//! [`LzoWriter`] takes a sequence of literal runs and matches, checks each
+//! against the constraints of the format and emits it with the shortest
+//! encoding. Instructions are encoded the same way the compressor does, so
+//! the parse of [`compress`](crate::compress) fed through the writer results
+//! in the same bytes.
+//!
+//! ## Example
+//! ```rust
+//! use lzokay_native::writer::LzoWriter;
+//!
+//! fn main() -> Result<(), lzokay_native::Error> {
+//!     let mut writer = LzoWriter::default();
+//!     writer.literals(b"abcd")?;
+//!     writer.lookback(12, 4)?;
+//!     writer.literals(b"e")?;
+//!     let stream = writer.finish();
+//!
+//!     let decompressed = lzokay_native::decompress_all(&stream, None)?;
+//!     assert_eq!(decompressed, b"abcdabcdabcdabcde");
+//!
+//!     Ok(())
+//! }
+//! ```
+
+use crate::util::{M3_MARKER, M4_MARKER};
+
+/// Largest distance of a match.
+pub const MAX_DISTANCE: usize = 0xbfff;
+
+/// Serializes literal runs and matches into a LZO1X instruction stream.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LzoWriter {
+    out: Vec<u8>,
+    start: usize,
+    /* Byte holding the S bits of the last match, if the last instruction was one */
+    state_pos: Option<usize>,
+    /* State the decoder is in after the last instruction */
+    state: usize,
+    output_len: usize,
+}
+
+impl Default for LzoWriter {
+    fn default() -> Self {
+        Self::new(Vec::new())
+    }
+}
+
+impl LzoWriter {
+    /// Creates a writer starting a stream after whatever `out` already contains.
+    #[must_use]
+    pub fn new(out: Vec<u8>) -> Self {
+        Self {
+            start: out.len(),
+            out,
+            state_pos: None,
+            state: 0,
+            output_len: 0,
+        }
+    }
+
+    /// Returns the number of bytes the stream decompresses to so far.
+    #[must_use]
+    pub const fn output_len(&self) -> usize {
+        self.output_len
+    }
+
+    /// Appends a run of literals. Empty runs are ignored.
+    ///
+    /// # Errors
+    /// Will return [`Err`] if the last instruction was a literal run, as two
+    /// runs can't follow each other.
+    pub fn literals(&mut self, lit: &[u8]) -> Result<(), crate::Error> {
+        if !lit.is_empty() && self.state_pos.is_none() && self.out.len() != self.start {
+            return Err(crate::Error::InvalidInstruction(
+                "literal run following a literal run",
+            ));
+        }
+        self.encode_literals(lit);
+        Ok(())
+    }
+
+    /// Appends a copy of `len` bytes from `distance` bytes back in the output.
+    ///
+    /// # Errors
+    /// Will return [`Err`] if the match reaches before the start of the
+    /// output or further than [`MAX_DISTANCE`], or can't be encoded. Matches
+    /// of 2 bytes need a distance of at most 1kB and 1 to 3 literals in front
+    /// of them.
+    pub fn lookback(&mut self, len: usize, distance: usize) -> Result<(), crate::Error> {
+        if distance == 0 || distance > self.output_len {
+            return Err(crate::Error::LookbehindOverrun);
+        }
+        if distance > MAX_DISTANCE {
+            return Err(crate::Error::InvalidInstruction(
+                "match distance exceeds 48kB",
+            ));
+        }
+        if len < 2 {
+            return Err(crate::Error::InvalidInstruction(
+                "match shorter than 2 bytes",
+            ));
+        }
+        if len == 2 && (distance > 0x400 || !(1..=3).contains(&self.state)) {
+            return Err(crate::Error::InvalidInstruction(
+                "2-byte match needs 1 to 3 literals in front and a distance within 1kB",
+            ));
+        }
+        self.encode_lookback(len, distance);
+        Ok(())
+    }
+
+    /* Appends a literal run without checking it can follow the last instruction */
+    pub(crate) fn encode_literals(&mut self, lit: &[u8]) {
+        let lit_len = lit.len();
+        if lit_len == 0 {
+            return;
+        }
+
+        if self.out.len() == self.start && lit_len <= 238 {
+            self.out.push((17 + lit_len) as u8);
+            self.state = lit_len.min(4);
+        } else if lit_len <= 3 {
+            if let Some(pos) = self.state_pos {
+                self.out[pos] |= lit_len as u8;
+            }
+            self.state = lit_len;
+        } else if lit_len <= 18 {
+            self.out.push((lit_len - 3) as u8);
+            self.state = 4;
+        } else {
+            self.out.push(0);
+            self.push_length(lit_len - 18);
+            self.state = 4;
+        }
+        self.out.extend_from_slice(lit);
+        self.state_pos = None;
+        self.output_len += lit_len;
+    }
+
+    /* Appends a match without checking it can be encoded */
+    pub(crate) fn encode_lookback(&mut self, len: usize, distance: usize) {
+        if len == 2 {
+            /* M1, 2 bytes within 1kB */
+            let off = distance - 1;
+            self.out.push(((off & 0x3) << 2) as u8);
+            self.out.push((off >> 2) as u8);
+        } else if len <= 8 && distance <= 0x800 {
+            /* M2 */
+            let off = distance - 1;
+            self.out.push((((len - 1) << 5) | ((off & 0x7) << 2)) as u8);
+            self.out.push((off >> 3) as u8);
+        } else if len == 3 && distance <= 0xc00 && self.state == 4 {
+            /* M1, 3 bytes within 2..3kB after a literal run */
+            let off = distance - 0x801;
+            self.out.push(((off & 0x3) << 2) as u8);
+            self.out.push((off >> 2) as u8);
+        } else if distance <= 0x4000 {
+            /* M3 */
+            let off = distance - 1;
+            if len <= 33 {
+                self.out.push((M3_MARKER as usize | (len - 2)) as u8);
+            } else {
+                self.out.push(M3_MARKER as u8);
+                self.push_length(len - 33);
+            }
+            self.out.push((off << 2) as u8);
+            self.out.push((off >> 6) as u8);
+        } else {
+            /* M4 */
+            let off = distance - 0x4000;
+            let h = ((off & 0x4000) >> 11) as u8;
+            if len <= 9 {
+                self.out.push(M4_MARKER as u8 | h | (len - 2) as u8);
+            } else {
+                self.out.push(M4_MARKER as u8 | h);
+                self.push_length(len - 9);
+            }
+            self.out.push((off << 2) as u8);
+            self.out.push((off >> 6) as u8);
+        }
+        self.state_pos = Some(self.out.len() - 2);
+        self.state = 0;
+        self.output_len += len;
+    }
+
+    /* lzo-rle zero run of MIN_ZERO_RUN_LENGTH..=MAX_ZERO_RUN_LENGTH bytes */
+    #[cfg(feature = "lzo-rle")]
+    pub(crate) fn zero_run(&mut self, len: usize) {
+        let run = len - crate::decompress::MIN_ZERO_RUN_LENGTH;
+        self.out.push(M4_MARKER as u8 | 0x8 | (run & 0x7) as u8);
+        self.out.push(0xfc);
+        self.out.push(0xff);
+        self.out.push((run >> 3) as u8);
+        self.state_pos = Some(self.out.len() - 3);
+        self.state = 0;
+        self.output_len += len;
+    }
+
+    /// Appends the terminating M4 and returns the stream.
+    #[must_use]
+    pub fn finish(mut self) -> Vec<u8> {
+        self.out.extend_from_slice(&[M4_MARKER as u8 | 1, 0, 0]);
+        self.out
+    }
+
+    /* (zero_bytes * 255) + non_zero_byte */
+    fn push_length(&mut self, mut len: usize) {
+        while len > 255 {
+            self.out.push(0);
+            len -= 255;
+        }
+        self.out.push(len as u8);
+    }
+}